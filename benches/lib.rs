@@ -3,6 +3,8 @@ extern crate quote;
 extern crate bencher;
 use quote::io::fs::*;
 use quote::paser::bcd::*;
+use quote::paser::checksum::{Validation, Validator};
+use quote::paser::fastbcd::*;
 use quote::paser::f6::*;
 
 use bencher::Bencher;
@@ -22,6 +24,61 @@ fn benchmark_bcd2volume(bencher: &mut Bencher) {
     bencher.iter(|| bcd2volume([0, 133, 32, 0]));
 }
 
+fn benchmark_try_bcd2num_price(bencher: &mut Bencher) {
+    bencher.iter(|| try_bcd2num(&[0, 133, 32, 0, 0]));
+}
+
+fn benchmark_try_bcd2num_volume(bencher: &mut Bencher) {
+    bencher.iter(|| try_bcd2num(&[0, 133, 32, 0]));
+}
+
+// trade plus five bids and five asks
+const LEVELS_RAW: &[u8] = &[
+    0x0, 0x5, 0x52, 0x0, 0x0, 0x0, 0x0, 0x0, 0x2, 0x0, 0x5, 0x45, 0x0, 0x0, 0x0, 0x0, 0x0, 0x1,
+    0x0, 0x5, 0x41, 0x0, 0x0, 0x0, 0x0, 0x0, 0x1, 0x0, 0x5, 0x40, 0x0, 0x0, 0x0, 0x0, 0x0, 0x1,
+    0x0, 0x5, 0x30, 0x0, 0x0, 0x0, 0x0, 0x0, 0x2, 0x0, 0x5, 0x22, 0x0, 0x0, 0x0, 0x0, 0x0, 0x24,
+    0x0, 0x5, 0x55, 0x0, 0x0, 0x0, 0x0, 0x0, 0x1, 0x0, 0x5, 0x58, 0x0, 0x0, 0x0, 0x0, 0x0, 0x1,
+    0x0, 0x5, 0x60, 0x0, 0x0, 0x0, 0x0, 0x0, 0x1, 0x0, 0x5, 0x61, 0x0, 0x0, 0x0, 0x0, 0x0, 0x2,
+    0x0, 0x5, 0x62, 0x0, 0x0, 0x0, 0x0, 0x0, 0x1,
+];
+
+fn benchmark_levels_bcd2price(bencher: &mut Bencher) {
+    bencher.iter(|| {
+        let mut out = [(0, 0); 11];
+        for (i, level) in out.iter_mut().enumerate() {
+            let raw = &LEVELS_RAW[i * 9..i * 9 + 9];
+            *level = (
+                bcd2price(raw[..5].try_into().unwrap()).scaled(),
+                bcd2volume(raw[5..].try_into().unwrap()),
+            );
+        }
+        out
+    });
+}
+
+fn bench_levels2num(bencher: &mut Bencher, backend: Backend) {
+    if !backend.is_available() {
+        return;
+    }
+    bencher.iter(|| {
+        let mut out = [(0, 0); 11];
+        try_levels2num_with(backend, LEVELS_RAW, &mut out).unwrap();
+        out
+    });
+}
+
+fn benchmark_levels2num_scalar(bencher: &mut Bencher) {
+    bench_levels2num(bencher, Backend::Scalar);
+}
+
+fn benchmark_levels2num_sse2(bencher: &mut Bencher) {
+    bench_levels2num(bencher, Backend::Sse2);
+}
+
+fn benchmark_levels2num_avx2(bencher: &mut Bencher) {
+    bench_levels2num(bencher, Backend::Avx2);
+}
+
 fn benchmark_bytes2quote(bencher: &mut Bencher) {
     bencher.iter(|| {
         bytes2quote(
@@ -71,14 +128,42 @@ fn benchmark_bytes2f6(bencher: &mut Bencher) {
     });
 }
 
+const F6_RAW: &[u8] = &[
+    0x1b, 0x1, 0x31, 0x1, 0x6, 0x4, 0x0, 0x10, 0x93, 0x59, 0x39, 0x31, 0x31, 0x36, 0x31,
+    0x36, 0x9, 0x0, 0x0, 0x14, 0x8, 0x66, 0xda, 0x0, 0x8, 0x0, 0x0, 0x0, 0x6, 0x0, 0x0,
+    0x1, 0x82, 0x0, 0x0, 0x0, 0x0, 0x6, 0x0, 0x0, 0x1, 0x82, 0x0, 0x0, 0x0, 0x0, 0x6, 0x0,
+    0x0, 0x1, 0x81, 0x0, 0x0, 0x0, 0x0, 0x5, 0x0, 0x0, 0x1, 0x80, 0x0, 0x0, 0x0, 0x0, 0x16,
+    0x0, 0x0, 0x1, 0x76, 0x0, 0x0, 0x0, 0x0, 0x28, 0x0, 0x0, 0x1, 0x75, 0x0, 0x0, 0x0, 0x0,
+    0x20, 0x0, 0x0, 0x1, 0x93, 0x0, 0x0, 0x0, 0x0, 0x8, 0x0, 0x0, 0x1, 0x94, 0x0, 0x0, 0x0,
+    0x0, 0x1, 0x0, 0x0, 0x1, 0x95, 0x0, 0x0, 0x0, 0x0, 0x1, 0x0, 0x0, 0x1, 0x96, 0x0, 0x0,
+    0x0, 0x0, 0x25, 0x0, 0x0, 0x1, 0x97, 0x0, 0x0, 0x0, 0x0, 0x26, 0xc6,
+];
+
+fn benchmark_f6ref_symbol(bencher: &mut Bencher) {
+    bencher.iter(|| F6Ref::new(F6_RAW).unwrap().symbol() == "911616");
+}
+
+fn benchmark_f6ref_trade(bencher: &mut Bencher) {
+    bencher.iter(|| F6Ref::new(F6_RAW).unwrap().trade());
+}
+
+fn benchmark_bytes2f6_symbol(bencher: &mut Bencher) {
+    bencher.iter(|| bytes2f6(F6_RAW).header.symbol() == "911616");
+}
+
+fn benchmark_f6ref_to_f6(bencher: &mut Bencher) {
+    bencher.iter(|| F6Ref::new(F6_RAW).unwrap().to_f6());
+}
+
 fn benchmark_readf6file(bencher: &mut Bencher) {
     use std::path::Path;
     fn f6handler(f6: F6) {
         // println!("{:?}", f6);
     }
     let path = Path::new("tests/data/f6_01000001_01001000_TP03.new");
+    let validator = Validator::new("bench", Validation::Lenient);
     bencher.iter(|| {
-        readf6file(&path, f6handler);
+        readf6file(&path, &validator, f6handler);
     });
 }
 
@@ -88,8 +173,9 @@ fn benchmark_readf6bufreader(bencher: &mut Bencher) {
         // println!("{:?}", f6);
     }
     let path = Path::new("tests/data/f6_01000001_01001000_TP03.new");
+    let validator = Validator::new("bench", Validation::Lenient);
     bencher.iter(|| {
-        readf6bufreader(&path, f6handler);
+        readf6bufreader(&path, &validator, f6handler);
     });
 }
 
@@ -99,8 +185,9 @@ fn benchmark_readf6filebuffer(bencher: &mut Bencher) {
         // println!("{:?}", f6);
     }
     let path = Path::new("tests/data/f6_01000001_01001000_TP03.new");
+    let validator = Validator::new("bench", Validation::Lenient);
     bencher.iter(|| {
-        readf6filebuffer(&path, f6handler);
+        readf6filebuffer(&path, &validator, f6handler);
     });
 }
 
@@ -110,9 +197,19 @@ benchmark_group!(
     benchmark_bcd2num,
     benchmark_bcd2price,
     benchmark_bcd2volume,
+    benchmark_try_bcd2num_price,
+    benchmark_try_bcd2num_volume,
+    benchmark_levels_bcd2price,
+    benchmark_levels2num_scalar,
+    benchmark_levels2num_sse2,
+    benchmark_levels2num_avx2,
     benchmark_bytes2quote,
     benchmark_bytes2f6header,
     benchmark_bytes2f6,
+    benchmark_f6ref_symbol,
+    benchmark_f6ref_trade,
+    benchmark_bytes2f6_symbol,
+    benchmark_f6ref_to_f6,
     benchmark_readf6file,
     benchmark_readf6bufreader,
     benchmark_readf6filebuffer,
@@ -0,0 +1,24 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use quote::paser::f6::{try_bytes2f6, F6Ref};
+
+fuzz_target!(|data: &[u8]| {
+    // both decoders accept exactly the same records
+    let (f6, f6ref) = match (try_bytes2f6(data), F6Ref::new(data)) {
+        (Ok(f6), Ok(f6ref)) => (f6, f6ref),
+        (Err(_), Err(_)) => return,
+        (Ok(f6), Err(e)) => panic!("accepted by try_bytes2f6 only: {:?} {:?}", f6, e),
+        (Err(e), Ok(f6ref)) => panic!("accepted by F6Ref::new only: {:?} {:?}", f6ref, e),
+    };
+    assert_eq!(f6.header.no, f6ref.no());
+    assert_eq!(f6.header.symbol, f6ref.symbol());
+    assert_eq!(f6.header.time, f6ref.time());
+    assert_eq!(f6.header.volsum, f6ref.volsum());
+    assert_eq!(f6.header.status, f6ref.status());
+    assert_eq!(f6.trade(), f6ref.trade());
+    for i in 0..6 {
+        assert_eq!(f6.quote.bidask.bid.get(i).copied(), f6ref.bid(i));
+        assert_eq!(f6.quote.bidask.ask.get(i).copied(), f6ref.ask(i));
+    }
+    assert_eq!(Ok(f6), f6ref.to_f6());
+});
@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use quote::paser::f6::try_bytes2header;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(header) = try_bytes2header(data) {
+        let (n_match, n_bid, n_ask) = header.n_info();
+        assert!(n_match <= 1 && n_bid <= 5 && n_ask <= 5);
+    }
+});
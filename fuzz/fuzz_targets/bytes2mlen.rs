@@ -0,0 +1,9 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use quote::paser::f6::{bytes2mlen, try_bytes2mlen};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(mlen) = try_bytes2mlen(data) {
+        assert_eq!(mlen, bytes2mlen(data));
+    }
+});
@@ -0,0 +1,17 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use quote::paser::f6::{try_bytes2quote, LEVEL_LEN};
+
+// The first three bytes pick the level counts, out of range ones included.
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 3 {
+        return;
+    }
+    let (n_match, n_bid, n_ask) = (data[0] as usize, data[1] as usize, data[2] as usize);
+    let body = &data[3..];
+    if let Ok(quote) = try_bytes2quote(body, n_match, n_bid, n_ask) {
+        assert!(body.len() >= LEVEL_LEN * (n_match + n_bid + n_ask));
+        assert_eq!(n_bid, quote.bidask.bid.len());
+        assert_eq!(n_ask, quote.bidask.ask.len());
+    }
+});
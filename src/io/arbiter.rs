@@ -0,0 +1,293 @@
+use crate::paser::sequence::{is_reset, StreamKey};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// One of the redundant lines a feed is published on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Line {
+    A,
+    B,
+}
+
+impl Line {
+    fn idx(self) -> usize {
+        match self {
+            Line::A => 0,
+            Line::B => 1,
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Line::A => f.write_str("A"),
+            Line::B => f.write_str("B"),
+        }
+    }
+}
+
+/// `won` counts the records this line delivered first, `lost` the sequence numbers it
+/// skipped, whether or not the other line had them.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LineStats {
+    pub received: u64,
+    pub won: u64,
+    pub lost: u64,
+}
+
+#[derive(Debug)]
+struct Stream<T> {
+    next: u64,
+    line_next: [Option<u64>; 2],
+    pending: BTreeMap<u64, T>,
+}
+
+impl<T> Stream<T> {
+    fn release(&mut self, out: &mut Vec<T>) {
+        while let Some(item) = self.pending.remove(&self.next) {
+            out.push(item);
+            self.next += 1;
+        }
+    }
+
+    // gives up on the records missing before the first pending one
+    fn skip_gap(&mut self, out: &mut Vec<T>) -> u64 {
+        let first = match self.pending.keys().next() {
+            Some(&first) => first,
+            None => return 0,
+        };
+        let skipped = first - self.next;
+        self.next = first;
+        self.release(out);
+        skipped
+    }
+
+    // the exchange numbers the stream anew from `seq`, whatever still waits is let through
+    fn restart(&mut self, seq: u64, out: &mut Vec<T>) -> u64 {
+        let mut skipped = 0;
+        while !self.pending.is_empty() {
+            skipped += self.skip_gap(out);
+        }
+        self.next = seq;
+        skipped
+    }
+}
+
+/// Merges the records of two lines into one stream per format, each sequence number once
+/// and in order. A record ahead of a gap is held until the other line fills the gap, or
+/// until more than `max_pending` records wait or `skip_gaps` is called, after which the gap
+/// is counted as lost on both lines.
+#[derive(Debug)]
+pub struct Arbiter<T> {
+    streams: HashMap<StreamKey, Stream<T>>,
+    stats: [LineStats; 2],
+    max_pending: usize,
+    gap_lost: u64,
+}
+
+impl<T> Arbiter<T> {
+    pub fn new(max_pending: usize) -> Arbiter<T> {
+        Arbiter {
+            streams: HashMap::new(),
+            stats: [LineStats::default(); 2],
+            max_pending,
+            gap_lost: 0,
+        }
+    }
+
+    /// Takes a record from `line` and appends whatever is now in order to `out`. Records
+    /// behind the stream, already forwarded or given up on, are dropped, unless they are so
+    /// far behind that the stream is taken to be numbered anew and restarts from them.
+    pub fn push(&mut self, line: Line, key: StreamKey, seq: u64, item: T, out: &mut Vec<T>) {
+        let stats = &mut self.stats[line.idx()];
+        stats.received += 1;
+        // the first record of a stream starts it, whichever line brings it
+        let stream = self.streams.entry(key).or_insert_with(|| Stream {
+            next: seq,
+            line_next: [None; 2],
+            pending: BTreeMap::new(),
+        });
+        let line_next = &mut stream.line_next[line.idx()];
+        // losses of a line count from its own first record, or from where it was renumbered
+        let expected = match *line_next {
+            Some(next) if !is_reset(next, seq) => next,
+            _ => seq,
+        };
+        if seq > expected {
+            stats.lost += seq - expected;
+        }
+        *line_next = Some(expected.max(seq + 1));
+        if is_reset(stream.next, seq) {
+            log::warn!("line {} restarted {:?} at #{}", line, key, seq);
+            self.gap_lost += stream.restart(seq, out);
+        }
+        if seq < stream.next || stream.pending.contains_key(&seq) {
+            return;
+        }
+        stats.won += 1;
+        log::debug!("line {} won {:?} #{}", line, key, seq);
+        stream.pending.insert(seq, item);
+        stream.release(out);
+        if stream.pending.len() > self.max_pending {
+            self.gap_lost += stream.skip_gap(out);
+        }
+    }
+
+    /// Gives up on every open gap, e.g. after waiting too long for the other line.
+    pub fn skip_gaps(&mut self, out: &mut Vec<T>) {
+        for stream in self.streams.values_mut() {
+            self.gap_lost += stream.skip_gap(out);
+        }
+    }
+
+    pub fn has_gap(&self) -> bool {
+        self.streams
+            .values()
+            .any(|stream| !stream.pending.is_empty())
+    }
+
+    pub fn stats(&self, line: Line) -> LineStats {
+        self.stats[line.idx()]
+    }
+
+    /// Records missing on both lines.
+    pub fn gap_lost(&self) -> u64 {
+        self.gap_lost
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use test_case::test_case;
+
+    const KEY: StreamKey = (1, 6);
+
+    fn run(arbiter: &mut Arbiter<u64>, input: &[(Line, u64)]) -> Vec<u64> {
+        let mut out = Vec::new();
+        for &(line, seq) in input {
+            arbiter.push(line, KEY, seq, seq, &mut out);
+        }
+        out
+    }
+
+    #[test_case(&[(Line::A, 1), (Line::A, 2), (Line::A, 3)], vec![1, 2, 3], (3, 0), (0, 0); "a only")]
+    #[test_case(&[(Line::A, 1), (Line::B, 1), (Line::B, 2), (Line::A, 2)], vec![1, 2], (1, 0), (1, 0); "duplicates")]
+    #[test_case(&[(Line::A, 1), (Line::A, 3), (Line::B, 1), (Line::B, 2), (Line::B, 3)], vec![1, 2, 3], (2, 1), (1, 0); "b fills a gap")]
+    #[test_case(&[(Line::A, 5), (Line::B, 7), (Line::A, 6)], vec![5, 6, 7], (2, 0), (1, 0); "b ahead")]
+    fn arbiter_testcase(
+        input: &[(Line, u64)],
+        expected: Vec<u64>,
+        (a_won, a_lost): (u64, u64),
+        (b_won, b_lost): (u64, u64),
+    ) {
+        let mut arbiter = Arbiter::new(8);
+        assert_eq!(expected, run(&mut arbiter, input));
+        assert!(!arbiter.has_gap());
+        let (a, b) = (arbiter.stats(Line::A), arbiter.stats(Line::B));
+        assert_eq!(
+            (a_won, a_lost, b_won, b_lost),
+            (a.won, a.lost, b.won, b.lost)
+        );
+    }
+
+    #[test]
+    fn arbiter_max_pending_test() {
+        let mut arbiter = Arbiter::new(2);
+        assert_eq!(
+            vec![1],
+            run(&mut arbiter, &[(Line::A, 1), (Line::A, 4), (Line::A, 5)])
+        );
+        assert!(arbiter.has_gap());
+        // a third waiting record gives up on 2 and 3
+        assert_eq!(vec![4, 5, 6], run(&mut arbiter, &[(Line::A, 6)]));
+        assert_eq!(2, arbiter.gap_lost());
+        // too late
+        assert_eq!(Vec::<u64>::new(), run(&mut arbiter, &[(Line::B, 2)]));
+        assert_eq!(vec![7], run(&mut arbiter, &[(Line::B, 7)]));
+        assert_eq!(
+            (2, 4),
+            (arbiter.stats(Line::A).lost, arbiter.stats(Line::B).lost)
+        );
+    }
+
+    #[test]
+    fn arbiter_skip_gaps_test() {
+        let mut arbiter = Arbiter::new(100);
+        let mut out = Vec::new();
+        arbiter.push(Line::A, KEY, 1, 1, &mut out);
+        arbiter.push(Line::A, KEY, 3, 3, &mut out);
+        arbiter.push(Line::B, (1, 1), 10, 10, &mut out);
+        arbiter.push(Line::B, (1, 1), 12, 12, &mut out);
+        assert_eq!(vec![1, 10], out);
+        arbiter.skip_gaps(&mut out);
+        out.sort();
+        assert_eq!(vec![1, 3, 10, 12], out);
+        assert_eq!((false, 2), (arbiter.has_gap(), arbiter.gap_lost()));
+    }
+
+    #[test]
+    fn arbiter_reset_test() {
+        let mut arbiter = Arbiter::new(100);
+        let input = [
+            (Line::A, 499_999),
+            (Line::B, 499_999),
+            (Line::A, 500_000),
+            (Line::A, 500_002),
+            // numbered anew, 500_002 is let through first
+            (Line::A, 1),
+            (Line::B, 1),
+            (Line::B, 2),
+            (Line::A, 2),
+        ];
+        assert_eq!(
+            vec![499_999, 500_000, 500_002, 1, 2],
+            run(&mut arbiter, &input)
+        );
+        assert_eq!((false, 1), (arbiter.has_gap(), arbiter.gap_lost()));
+        // neither line counts the restart as a loss
+        let (a, b) = (arbiter.stats(Line::A), arbiter.stats(Line::B));
+        assert_eq!((4, 1, 1, 0), (a.won, a.lost, b.won, b.lost));
+    }
+
+    proptest! {
+        #[test]
+        fn arbiter_forwards_each_once_in_order_proptest(
+            mut drops in prop::collection::vec(0..3u8, 1..200),
+            lag in 0..5usize,
+        ) {
+            // a stream starts at the first record seen, so both lines carry it
+            drops[0] = 0;
+            // 0: both lines deliver, 1: A drops it, 2: B drops it; B runs `lag` records behind
+            let a: Vec<(Line, u64)> = (1..).zip(&drops)
+                .filter(|(_, &drop)| drop != 1)
+                .map(|(seq, _)| (Line::A, seq))
+                .collect();
+            let b: Vec<(Line, u64)> = (1..).zip(&drops)
+                .filter(|(_, &drop)| drop != 2)
+                .map(|(seq, _)| (Line::B, seq))
+                .collect();
+            let mut input = Vec::new();
+            let (mut ia, mut ib) = (0, 0);
+            while ia < a.len() || ib < b.len() {
+                if ia < a.len() && (ib >= b.len() || a[ia].1 <= b[ib].1 + lag as u64) {
+                    input.push(a[ia]);
+                    ia += 1;
+                } else {
+                    input.push(b[ib]);
+                    ib += 1;
+                }
+            }
+            let mut arbiter = Arbiter::new(16);
+            let out = run(&mut arbiter, &input);
+            prop_assert_eq!((1..=drops.len() as u64).collect::<Vec<u64>>(), out);
+            let (sa, sb) = (arbiter.stats(Line::A), arbiter.stats(Line::B));
+            prop_assert_eq!(drops.len() as u64, sa.won + sb.won);
+            // a line cannot see its own losses after its last record
+            prop_assert!(sa.lost <= drops.iter().filter(|&&drop| drop == 1).count() as u64);
+            prop_assert!(sb.lost <= drops.iter().filter(|&&drop| drop == 2).count() as u64);
+        }
+    }
+}
@@ -0,0 +1,438 @@
+use crate::paser::bcd;
+use crate::paser::checksum::TERMINATOR;
+use crate::paser::f6::ESC;
+use crate::paser::taifex;
+use std::io::{self, Read};
+use std::ops::AddAssign;
+
+// ESC, mlen, cate, fcode, fver, seq no, checksum and CR/LF
+pub const MIN_RECORD_LEN: usize = 13;
+pub const MAX_RECORD_LEN: usize = 4096;
+const READ_CHUNK: usize = 64 * 1024;
+
+/// How a record states its length. TWSE and TPEx carry the full length in the 2 bytes after
+/// ESC, TAIFEX carries the body length at the end of its 16 byte header.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    #[default]
+    Twse,
+    Taifex,
+}
+
+impl Protocol {
+    fn prefix_len(self) -> usize {
+        match self {
+            Protocol::Twse => 3,
+            Protocol::Taifex => taifex::HEADER_LEN,
+        }
+    }
+
+    fn min_len(self) -> usize {
+        match self {
+            Protocol::Twse => MIN_RECORD_LEN,
+            // header, checksum and CR/LF
+            Protocol::Taifex => taifex::HEADER_LEN + 3,
+        }
+    }
+
+    // `rest` holds at least `prefix_len` bytes
+    fn record_len(self, rest: &[u8]) -> Option<usize> {
+        match self {
+            Protocol::Twse => bcd::try_bcdarr2num(&rest[1..3])
+                .ok()
+                .map(|len| len as usize),
+            Protocol::Taifex => taifex::try_bytes2len(rest).ok(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FramerStats {
+    pub records: u64,
+    pub skipped_bytes: u64,
+    pub resyncs: u64,
+}
+
+impl FramerStats {
+    fn skip(&mut self, n: usize) {
+        if n > 0 {
+            self.skipped_bytes += n as u64;
+            self.resyncs += 1;
+        }
+    }
+}
+
+impl AddAssign for FramerStats {
+    fn add_assign(&mut self, rhs: FramerStats) {
+        self.records += rhs.records;
+        self.skipped_bytes += rhs.skipped_bytes;
+        self.resyncs += rhs.resyncs;
+    }
+}
+
+enum Scan {
+    Record { skip: usize, len: usize },
+    NeedMore { skip: usize },
+}
+
+// A candidate starts with ESC, has a BCD length within bounds and ends in CR/LF; anything
+// else is skipped one byte at a time until the next ESC. Without `eof`, a candidate that
+// runs past the end of `data` waits for more bytes instead.
+fn scan(protocol: Protocol, data: &[u8], eof: bool, max_len: usize) -> Scan {
+    let mut pos = 0;
+    while let Some(offset) = data[pos..].iter().position(|&b| b == ESC) {
+        let start = pos + offset;
+        let rest = &data[start..];
+        pos = start + 1;
+        if rest.len() < protocol.prefix_len() {
+            if eof {
+                continue;
+            }
+            return Scan::NeedMore { skip: start };
+        }
+        let len = match protocol.record_len(rest) {
+            Some(len) => len,
+            None => continue,
+        };
+        if !(protocol.min_len()..=max_len).contains(&len) {
+            continue;
+        }
+        if rest.len() < len {
+            if eof {
+                continue;
+            }
+            return Scan::NeedMore { skip: start };
+        }
+        if rest[len - 2..len] != TERMINATOR {
+            continue;
+        }
+        return Scan::Record { skip: start, len };
+    }
+    Scan::NeedMore { skip: data.len() }
+}
+
+/// Splits a byte stream into records of one protocol, TWSE unless set otherwise. Bytes can
+/// arrive in any chunking through `push` or `read_from`; garbage between records is skipped
+/// and counted.
+#[derive(Debug)]
+pub struct Framer {
+    buf: Vec<u8>,
+    start: usize,
+    eof: bool,
+    max_len: usize,
+    protocol: Protocol,
+    stats: FramerStats,
+}
+
+impl Default for Framer {
+    fn default() -> Framer {
+        Framer::new()
+    }
+}
+
+impl Framer {
+    pub fn new() -> Framer {
+        Framer::with_max_len(MAX_RECORD_LEN)
+    }
+
+    pub fn with_max_len(max_len: usize) -> Framer {
+        Framer {
+            buf: Vec::new(),
+            start: 0,
+            eof: false,
+            max_len,
+            protocol: Protocol::Twse,
+            stats: FramerStats::default(),
+        }
+    }
+
+    pub fn with_protocol(protocol: Protocol) -> Framer {
+        Framer {
+            protocol,
+            ..Framer::new()
+        }
+    }
+
+    fn compact(&mut self) {
+        if self.start > 0 {
+            self.buf.drain(..self.start);
+            self.start = 0;
+        }
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.compact();
+        self.buf.extend_from_slice(data);
+        self.eof = false;
+    }
+
+    /// Reads one chunk from `reader`, returning 0 and marking the end of input at EOF.
+    pub fn read_from<R: Read>(&mut self, reader: &mut R) -> io::Result<usize> {
+        self.compact();
+        let old = self.buf.len();
+        self.buf.resize(old + READ_CHUNK, 0);
+        let read = reader.read(&mut self.buf[old..]);
+        self.buf.truncate(old + *read.as_ref().unwrap_or(&0));
+        if let Ok(0) = read {
+            self.eof = true;
+        }
+        read
+    }
+
+    /// No more bytes will come; a trailing partial record is dropped as skipped.
+    pub fn finish(&mut self) {
+        self.eof = true;
+    }
+
+    pub fn pending(&self) -> usize {
+        self.buf.len() - self.start
+    }
+
+    pub fn stats(&self) -> FramerStats {
+        self.stats
+    }
+
+    pub fn next_record(&mut self) -> Option<&[u8]> {
+        match scan(
+            self.protocol,
+            &self.buf[self.start..],
+            self.eof,
+            self.max_len,
+        ) {
+            Scan::Record { skip, len } => {
+                self.stats.skip(skip);
+                self.stats.records += 1;
+                let start = self.start + skip;
+                self.start = start + len;
+                Some(&self.buf[start..start + len])
+            }
+            Scan::NeedMore { skip } => {
+                self.stats.skip(skip);
+                self.start += skip;
+                None
+            }
+        }
+    }
+}
+
+/// Frames a complete buffer such as a datagram or a mapped file without copying.
+pub struct Records<'a> {
+    data: &'a [u8],
+    max_len: usize,
+    protocol: Protocol,
+    stats: FramerStats,
+}
+
+pub fn records(data: &[u8]) -> Records<'_> {
+    records_with(Protocol::Twse, data)
+}
+
+pub fn records_with(protocol: Protocol, data: &[u8]) -> Records<'_> {
+    Records {
+        data,
+        max_len: MAX_RECORD_LEN,
+        protocol,
+        stats: FramerStats::default(),
+    }
+}
+
+impl<'a> Records<'a> {
+    pub fn stats(&self) -> FramerStats {
+        self.stats
+    }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        match scan(self.protocol, self.data, true, self.max_len) {
+            Scan::Record { skip, len } => {
+                self.stats.skip(skip);
+                self.stats.records += 1;
+                let record = &self.data[skip..skip + len];
+                self.data = &self.data[skip + len..];
+                Some(record)
+            }
+            Scan::NeedMore { skip } => {
+                self.stats.skip(skip);
+                self.data = &[];
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paser::fixtures::{F6_REC as REC, I020_RAW as TAIFEX_REC};
+    use proptest::prelude::*;
+    use test_case::test_case;
+
+    fn stream(parts: &[&[u8]]) -> Vec<u8> {
+        parts.concat()
+    }
+
+    #[test_case(stream(&[REC, REC, REC]), 3, 0, 0; "clean")]
+    #[test_case(stream(&[&[0x0, 0x13, 0x37], REC, REC]), 2, 3, 1; "garbage prefix")]
+    #[test_case(stream(&[REC, &[0x1b, 0x0, 0x5, 0x1], REC]), 2, 4, 1; "mlen below minimum")]
+    #[test_case(stream(&[REC, &[0x1b, 0x99, 0x99, 0x1], REC]), 2, 4, 1; "mlen above maximum")]
+    #[test_case(stream(&[REC, &[0x1b, 0x0, 0x41], &REC[3..20], REC]), 2, 20, 1; "mlen points into next record")]
+    #[test_case(stream(&[&REC[..40], REC]), 1, 40, 1; "bad terminator")]
+    #[test_case(stream(&[REC, &[0x1b, 0x3a, 0x0], REC]), 2, 3, 1; "invalid bcd mlen")]
+    #[test_case(stream(&[REC, REC, &REC[..30]]), 2, 30, 1; "truncated tail")]
+    fn records_testcase(input: Vec<u8>, n_records: u64, skipped: u64, resyncs: u64) {
+        let mut records = records(&input);
+        for raw in records.by_ref() {
+            assert_eq!(REC, raw);
+        }
+        assert_eq!(
+            FramerStats {
+                records: n_records,
+                skipped_bytes: skipped,
+                resyncs,
+            },
+            records.stats()
+        );
+    }
+
+    #[test]
+    fn framer_split_push_test() {
+        let input = stream(&[&[0xff], REC, REC, REC]);
+        let mut framer = Framer::new();
+        let mut count = 0;
+        for chunk in input.chunks(7) {
+            framer.push(chunk);
+            while let Some(raw) = framer.next_record() {
+                assert_eq!(REC, raw);
+                count += 1;
+            }
+        }
+        framer.finish();
+        assert_eq!(None, framer.next_record());
+        assert_eq!(3, count);
+        assert_eq!(
+            (3, 1, 0),
+            (
+                framer.stats().records,
+                framer.stats().skipped_bytes,
+                framer.pending()
+            )
+        );
+    }
+
+    #[test]
+    fn framer_finish_drops_partial_test() {
+        let mut framer = Framer::new();
+        framer.push(&stream(&[REC, &REC[..30]]));
+        assert_eq!(Some(REC), framer.next_record());
+        assert_eq!(None, framer.next_record());
+        assert_eq!(30, framer.pending());
+        framer.finish();
+        assert_eq!(None, framer.next_record());
+        assert_eq!((0, 30), (framer.pending(), framer.stats().skipped_bytes));
+    }
+
+    #[test]
+    fn framer_read_from_file_test() {
+        let mut file = std::fs::File::open("tests/data/f6_01000001_01001000_TP03.new").unwrap();
+        let mut framer = Framer::new();
+        let mut count = 0;
+        while framer.read_from(&mut file).unwrap() > 0 {
+            while framer.next_record().is_some() {
+                count += 1;
+            }
+        }
+        while framer.next_record().is_some() {
+            count += 1;
+        }
+        assert_eq!(1000, count);
+        assert_eq!(0, framer.stats().skipped_bytes);
+    }
+
+    #[test_case(stream(&[TAIFEX_REC, TAIFEX_REC]), 2, 0; "clean")]
+    #[test_case(stream(&[&[0x1b, 0x32], TAIFEX_REC, REC, TAIFEX_REC]), 2, 43; "garbage and twse record")]
+    #[test_case(stream(&[TAIFEX_REC, &TAIFEX_REC[..40]]), 1, 40; "truncated tail")]
+    fn records_taifex_testcase(input: Vec<u8>, n_records: u64, skipped: u64) {
+        let mut records = records_with(Protocol::Taifex, &input);
+        for raw in records.by_ref() {
+            assert_eq!(TAIFEX_REC, raw);
+        }
+        assert_eq!(
+            (n_records, skipped),
+            (records.stats().records, records.stats().skipped_bytes)
+        );
+    }
+
+    #[test]
+    fn framer_taifex_split_push_test() {
+        let input = stream(&[TAIFEX_REC, TAIFEX_REC]);
+        let mut framer = Framer::with_protocol(Protocol::Taifex);
+        let mut count = 0;
+        for chunk in input.chunks(5) {
+            framer.push(chunk);
+            while let Some(raw) = framer.next_record() {
+                assert_eq!(TAIFEX_REC, raw);
+                count += 1;
+            }
+        }
+        assert_eq!((2, 0), (count, framer.pending()));
+    }
+
+    proptest! {
+        #[test]
+        fn records_accounts_every_byte_proptest(data in prop::collection::vec(any::<u8>(), 0..512)) {
+            let mut records = records(&data);
+            let mut framed = 0;
+            for raw in records.by_ref() {
+                prop_assert_eq!(ESC, raw[0]);
+                prop_assert_eq!(Ok(raw.len() as u64), bcd::try_bcdarr2num(&raw[1..3]));
+                prop_assert_eq!(&TERMINATOR[..], &raw[raw.len() - 2..]);
+                framed += raw.len();
+            }
+            prop_assert_eq!(data.len() as u64, framed as u64 + records.stats().skipped_bytes);
+        }
+
+        #[test]
+        fn records_resync_after_garbage_proptest(
+            parts in prop::collection::vec(prop::collection::vec(1..=255u8, 0..20), 1..8),
+        ) {
+            // garbage without ESC can never start a record
+            let parts: Vec<Vec<u8>> = parts
+                .into_iter()
+                .map(|part| part.into_iter().filter(|&b| b != ESC).collect())
+                .collect();
+            let garbage: usize = parts.iter().map(Vec::len).sum();
+            let data = parts.join(REC);
+            let mut records = records(&data);
+            for raw in records.by_ref() {
+                prop_assert_eq!(REC, raw);
+            }
+            prop_assert_eq!(parts.len() as u64 - 1, records.stats().records);
+            prop_assert_eq!(garbage as u64, records.stats().skipped_bytes);
+        }
+
+        #[test]
+        fn framer_matches_records_proptest(
+            data in prop::collection::vec(any::<u8>(), 0..512),
+            chunk in 1..64usize,
+        ) {
+            let expected: Vec<&[u8]> = records(&data).collect();
+            let mut framer = Framer::new();
+            let mut framed = Vec::new();
+            for part in data.chunks(chunk) {
+                framer.push(part);
+                while let Some(raw) = framer.next_record() {
+                    framed.push(raw.to_vec());
+                }
+            }
+            framer.finish();
+            while let Some(raw) = framer.next_record() {
+                framed.push(raw.to_vec());
+            }
+            prop_assert_eq!(expected, framed);
+            prop_assert_eq!(0, framer.pending());
+        }
+    }
+}
@@ -1,115 +1,101 @@
 use std::fs::File;
-use std::io::BufReader;
 use std::path::Path;
 // use std::io::prelude::*;
-use crate::paser::f6::{bytes2header, bytes2mlen, bytes2quote, bytes2f6, F6};
+use crate::io::framer::{records, Framer, FramerStats};
+use crate::paser::checksum::Validator;
+use crate::paser::f6::{try_bytes2f6, F6};
 use filebuffer::FileBuffer;
-use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::io::Read;
 use rayon::prelude::*;
 
-pub fn readf6file(path: &Path, rec_handler: fn(F6)) {
+fn handle_record(raw: &[u8], validator: &Validator, rec_handler: fn(F6)) {
+    if validator.check(raw).is_err() {
+        return;
+    }
+    match try_bytes2f6(raw) {
+        Ok(f6) => rec_handler(f6),
+        Err(e) => log::warn!("skip record: {}", e),
+    }
+}
+
+fn log_stats(path: &Path, stats: FramerStats) {
+    if stats.skipped_bytes > 0 {
+        log::warn!(
+            "{}: skipped {} bytes in {} resyncs",
+            path.display(),
+            stats.skipped_bytes,
+            stats.resyncs
+        );
+    }
+}
+
+pub fn readf6file(path: &Path, validator: &Validator, rec_handler: fn(F6)) {
     let display = path.display();
     let mut file = match File::open(&path) {
         Err(why) => panic!("couldn't open {}: {}", display, why),
         Ok(file) => (file),
     };
-    let file_size = file.metadata().unwrap().len();
-    let mut fbuffer = vec![0u8; file_size as usize];
-    file.read(&mut fbuffer).unwrap();
-    let mut buf = [0u8; 256];
-    let mut c = Cursor::new(fbuffer);
-    c.seek(SeekFrom::Start(0)).unwrap();
-    loop {
-        if c.position() == file_size {
-            break;
-        }
-        c.read_exact(&mut buf[..4]).unwrap();
-        let mlen = bytes2mlen(&buf);
-        c.read_exact(&mut buf[4..mlen]).unwrap();
-        let h = bytes2header(&buf);
-        // println!("header: {:?}", h);
-        let (n_match, n_bid, n_ask) = h.n_info();
-        let f6 = F6 {
-            header: h,
-            quote: bytes2quote(&buf[29..mlen], n_match, n_bid, n_ask),
-        };
-        rec_handler(f6);
+    let mut fbuffer = Vec::new();
+    file.read_to_end(&mut fbuffer).unwrap();
+    let mut records = records(&fbuffer);
+    for raw in records.by_ref() {
+        handle_record(raw, validator, rec_handler);
     }
+    log_stats(path, records.stats());
 }
 
-pub fn readf6bufreader(path: &Path, rec_handler: fn(F6)) {
+pub fn readf6bufreader(path: &Path, validator: &Validator, rec_handler: fn(F6)) {
     let display = path.display();
-    let file = match File::open(&path) {
+    let mut file = match File::open(&path) {
         Err(why) => panic!("couldn't open {}: {}", display, why),
         Ok(file) => (file),
     };
-    let file_size = file.metadata().unwrap().len();
-    let mut reader = BufReader::new(file);
-    let mut buf = [0u8; 256];
-    // c.seek(SeekFrom::Start(0)).unwrap();
+    let mut framer = Framer::new();
     loop {
-        if reader.stream_position().unwrap() == file_size {
+        let read = match framer.read_from(&mut file) {
+            Ok(read) => read,
+            Err(e) => {
+                log::error!("stop reading {}: {}", display, e);
+                framer.finish();
+                0
+            }
+        };
+        while let Some(raw) = framer.next_record() {
+            handle_record(raw, validator, rec_handler);
+        }
+        if read == 0 {
             break;
         }
-        reader.read_exact(&mut buf[..4]).unwrap();
-        let mlen = bytes2mlen(&buf);
-        reader.read_exact(&mut buf[4..mlen]).unwrap();
-        let h = bytes2header(&buf);
-        // println!("header: {:?}", h);
-        let (n_match, n_bid, n_ask) = h.n_info();
-        let f6 = F6 {
-            header: h,
-            quote: bytes2quote(&buf[29..mlen], n_match, n_bid, n_ask),
-        };
-        rec_handler(f6);
     }
+    log_stats(path, framer.stats());
 }
 
-pub fn readf6filebuffer(path: &Path, rec_handler: fn(F6)) {
+pub fn readf6filebuffer(path: &Path, validator: &Validator, rec_handler: fn(F6)) {
     // let display = path.display();
     let fbuffer = FileBuffer::open(&path).expect("failed to open file {}");
-    let fsize: u64 = fbuffer.len() as u64;
-    // let mut buf = [0u8; 256];
-    // let mut bufarr = vec![[0u8; 256]; ((fsize / 131) + 1) as usize];
     const BUFSIZE: usize = 2048;
-    let mut bufarr = [[0u8; 256]; BUFSIZE];
-    let mut c = Cursor::new(fbuffer);
-    c.seek(SeekFrom::Start(0)).unwrap();
-    let mut count = 0;
-    loop {
-        if c.position() == fsize {
-            bufarr[..count].into_par_iter().for_each(|x| {
-                let f6 = bytes2f6(x);
-                rec_handler(f6);
-            });
-            break;
-        }
-        c.read_exact(&mut bufarr[count][..4]).unwrap();
-        let mlen = bytes2mlen(&bufarr[count]);
-        c.read_exact(&mut bufarr[count][4..mlen]).unwrap();
-        count += 1;
-        if count == BUFSIZE {
-            bufarr.into_par_iter().for_each(|x| {
-                let f6 = bytes2f6(&x);
-                rec_handler(f6);
-            });
-            count = 0;
-            // bufarr.push([0u8; 256]);
+    let mut batch: Vec<&[u8]> = Vec::with_capacity(BUFSIZE);
+    let handle_batch = |batch: &[&[u8]]| {
+        batch.par_iter().for_each(|raw| {
+            handle_record(raw, validator, rec_handler);
+        });
+    };
+    let mut records = records(&fbuffer);
+    for raw in records.by_ref() {
+        batch.push(raw);
+        if batch.len() == BUFSIZE {
+            handle_batch(&batch);
+            batch.clear();
         }
-        // let h = bytes2header(&buf);
-        // // println!("header: {:?}", h);
-        // let (n_match, n_bid, n_ask) = h.n_info();
-        // let f6 = F6 {
-        //     header: h,
-        //     quote: bytes2quote(&buf[29..mlen], n_match, n_bid, n_ask),
-        // };
-        // rec_handler(f6);
     }
+    handle_batch(&batch);
+    log_stats(path, records.stats());
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::paser::checksum::Validation;
     // use test_case::test_case;
 
     #[test]
@@ -117,11 +103,13 @@ mod tests {
         fn f6handler(f6: F6) {
             println!("{:?}", f6);
         }
+        let validator = Validator::new("test", Validation::Strict);
         readf6file(
             &Path::new("tests/data/f6_01000001_01001000_TP03.new"),
+            &validator,
             f6handler,
         );
-        assert_eq!(1, 1)
+        assert_eq!((1000, 0), (validator.stats().checked(), validator.stats().corrupted()))
     }
 
     #[test]
@@ -129,11 +117,13 @@ mod tests {
         fn f6handler(f6: F6) {
             println!("{:?}", f6);
         }
+        let validator = Validator::new("test", Validation::Strict);
         readf6bufreader(
             &Path::new("tests/data/f6_01000001_01001000_TP03.new"),
+            &validator,
             f6handler,
         );
-        assert_eq!(1, 1)
+        assert_eq!((1000, 0), (validator.stats().checked(), validator.stats().corrupted()))
     }
 
     #[test]
@@ -141,10 +131,12 @@ mod tests {
         fn f6handler(f6: F6) {
             println!("{:?}", f6);
         }
+        let validator = Validator::new("test", Validation::Strict);
         readf6filebuffer(
             &Path::new("tests/data/f6_01000001_01001000_TP03.new"),
+            &validator,
             f6handler,
         );
-        assert_eq!(1, 1)
+        assert_eq!((1000, 0), (validator.stats().checked(), validator.stats().corrupted()))
     }
 }
@@ -1,14 +1,111 @@
 use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::io;
-use std::io::{Cursor, Read, Seek, SeekFrom};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
 // use std::sync::mpsc::Sender;
-// use crossbeam_channel::Sender;
-use bus::Bus as Sender;
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
 // use std::time::Duration;
-use crate::paser::f6::{bytes2fcode, bytes2header, bytes2mlen, bytes2quote, F6};
+use crate::paser::checksum::Validator;
+use crate::io::arbiter::{Arbiter, Line};
+use crate::io::framer::{self, records_with, FramerStats};
+use crate::io::recorder::RecorderHandle;
+use crate::io::recovery::{Recovered, RecoveryWorker, Sequencer};
+use crate::paser::market::Market;
+use crate::paser::message::Message;
+use crate::paser::registry::Registry;
+use crate::paser::sequence::{sequence, Gap, SeqResult, SequenceTracker, StreamKey};
+use crate::paser::taifex;
+use crate::paser::timestamp::RecvTime;
 // use chrono::prelude::Local;
 
+/// Senders a channel takes datagrams from, e.g. "10.3.0.1,10.4.0.1"; empty or "any" takes
+/// every sender.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceFilter {
+    sources: Vec<IpAddr>,
+}
+
+impl SourceFilter {
+    pub fn any() -> SourceFilter {
+        SourceFilter::default()
+    }
+
+    pub fn new(sources: Vec<IpAddr>) -> SourceFilter {
+        SourceFilter { sources }
+    }
+
+    pub fn sources(&self) -> &[IpAddr] {
+        &self.sources
+    }
+
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        self.sources.is_empty() || self.sources.contains(&ip)
+    }
+}
+
+impl FromStr for SourceFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() || s.trim().eq_ignore_ascii_case("any") {
+            return Ok(SourceFilter::any());
+        }
+        s.split(',')
+            .map(|source| {
+                source
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid source address: {}", source))
+            })
+            .collect::<Result<Vec<IpAddr>, String>>()
+            .map(SourceFilter::new)
+    }
+}
+
+/// Where the source filter is applied: `Ssm` joins the group per source so the kernel drops
+/// other senders, `Userland` joins any-source and checks each datagram after receiving it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceMode {
+    Off,
+    Userland,
+    Ssm,
+    Both,
+}
+
+impl SourceMode {
+    pub fn ssm(self) -> bool {
+        matches!(self, SourceMode::Ssm | SourceMode::Both)
+    }
+
+    pub fn userland(self) -> bool {
+        matches!(self, SourceMode::Userland | SourceMode::Both)
+    }
+}
+
+impl FromStr for SourceMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(SourceMode::Off),
+            "userland" => Ok(SourceMode::Userland),
+            "ssm" => Ok(SourceMode::Ssm),
+            "both" => Ok(SourceMode::Both),
+            _ => Err(format!("unknown source mode: {}", s)),
+        }
+    }
+}
+
+// records held back waiting for the other line to fill a gap
+const MAX_PENDING: usize = 4096;
+const STATS_EVERY: u64 = 100_000;
+// how often a listener holding records after a gap looks for the recovery's result
+const RECOVERY_POLL: Duration = Duration::from_millis(10);
+
 fn new_socket(addr: &SocketAddr) -> io::Result<Socket> {
     let domain = if addr.is_ipv4() {
         Domain::ipv4()
@@ -47,12 +144,85 @@ fn disable_multicast_all(udp_socket: &UdpSocket) {
 #[cfg(not(target_os = "linux"))]
 fn disable_multicast_all(_udp_socket: &UdpSocket) {}
 
+#[cfg(target_os = "linux")]
+fn join_source_v4(
+    socket: &Socket,
+    group: &Ipv4Addr,
+    interface: &Ipv4Addr,
+    source: &Ipv4Addr,
+) -> io::Result<()> {
+    use std::mem;
+    use std::os::unix::io::AsRawFd;
+    // in_addr holds the address in network byte order, i.e. the octets as they are
+    let in_addr = |ip: &Ipv4Addr| libc::in_addr {
+        s_addr: u32::from_ne_bytes(ip.octets()),
+    };
+    let mreq = libc::ip_mreq_source {
+        imr_multiaddr: in_addr(group),
+        imr_interface: in_addr(interface),
+        imr_sourceaddr: in_addr(source),
+    };
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_IP,
+            libc::IP_ADD_SOURCE_MEMBERSHIP,
+            &mreq as *const _ as *const libc::c_void,
+            mem::size_of_val(&mreq) as libc::socklen_t,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+#[cfg(not(target_os = "linux"))]
+fn join_source_v4(
+    _socket: &Socket,
+    _group: &Ipv4Addr,
+    _interface: &Ipv4Addr,
+    _source: &Ipv4Addr,
+) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "source-specific multicast is only supported on Linux",
+    ))
+}
+
 pub fn join_mcast(addr: &SocketAddr, interface: &SocketAddr) -> io::Result<UdpSocket> {
+    join_mcast_from(addr, interface, &SourceFilter::any())
+}
+
+/// Like `join_mcast`, but with sources in `sources` the group is joined once per source
+/// (IP_ADD_SOURCE_MEMBERSHIP) instead of for any sender. Only IPv4 groups and sources can be
+/// joined that way, anything else fails rather than falling back to any sender.
+pub fn join_mcast_from(
+    addr: &SocketAddr,
+    interface: &SocketAddr,
+    sources: &SourceFilter,
+) -> io::Result<UdpSocket> {
     let ip_arrd = addr.ip();
     let ip_interface = interface.ip();
+    let mut sources_v4: Vec<Ipv4Addr> = Vec::new();
+    for source in sources.sources() {
+        match (source, ip_arrd, ip_interface) {
+            (IpAddr::V4(source), IpAddr::V4(_), IpAddr::V4(_)) => sources_v4.push(*source),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("cannot join {} from {} on {}, IPv4 only", addr, source, interface),
+                ))
+            }
+        }
+    }
     let socket = new_socket(&addr).unwrap();
     match ip_arrd {
         IpAddr::V4(ref mdns_v4) => match ip_interface {
+            IpAddr::V4(ref if_v4) if !sources_v4.is_empty() => {
+                for source in sources_v4.iter() {
+                    join_source_v4(&socket, mdns_v4, if_v4, source)?;
+                }
+            }
             IpAddr::V4(ref if_v4) => {
                 socket.join_multicast_v4(mdns_v4, if_v4).unwrap();
             }
@@ -69,73 +239,914 @@ pub fn join_mcast(addr: &SocketAddr, interface: &SocketAddr) -> io::Result<UdpSo
     Ok(udp_socket)
 }
 
-pub fn process(socket: UdpSocket, sender: &mut Sender<F6>) {
+fn protocol_of(market: Market) -> framer::Protocol {
+    match market {
+        Market::Taifex => framer::Protocol::Taifex,
+        _ => framer::Protocol::Twse,
+    }
+}
+
+/// How a listener takes records in: the senders it accepts datagrams from, the validation of
+/// the records framed from them and the recorder capturing the records forwarded, if any.
+/// A redundant feed captures once, after arbitration, so its lines' intakes have no recorder.
+#[derive(Debug, Clone)]
+pub struct Intake {
+    pub filter: SourceFilter,
+    pub validator: Validator,
+    pub recorder: Option<RecorderHandle>,
+}
+
+// Receives datagrams from the allowed senders and hands every framed record that passes
+// validation to `handle`, with the receive time of its datagram, until `handle` returns false.
+// `idle` is called whenever the socket's read timeout passes without a datagram and stops the
+// loop the same way.
+fn recv_records<F: FnMut(RecvTime, &[u8]) -> bool, I: FnMut() -> bool>(
+    socket: &UdpSocket,
+    market: Market,
+    intake: &Intake,
+    mut handle: F,
+    mut idle: I,
+) {
     let mut fbuffer = [0u8; 4096];
     // let mut fbuffer_ = [0u8; 4096];
     // let mut c = Cursor::new(Vec::new());
     // let mut header = [0u8; 29];
-    let filter_ip = Ipv4Addr::new(10, 3, 0, 1);
-    let mut count = 0;
-    let mut buf = [0u8; 512];
+    let mut framer_stats = FramerStats::default();
+    let protocol = protocol_of(market);
     loop {
         match socket.recv_from(&mut fbuffer) {
             Ok((received, rec_addr)) => {
-                let rec_ip = rec_addr.ip();
-                match rec_ip {
-                    IpAddr::V4(ref rec_ip_v4) => {
-                        if rec_ip_v4 == &filter_ip {
-                            // println!("{:?}", rec_addr);
-                            // println!("received {} bytes {:?}", received, &fbuffer[..received]);
-                            log::debug!("received {} bytes {:?}", received, &fbuffer[..received]);
-                            let mut c = Cursor::new(fbuffer);
-                            c.seek(SeekFrom::Start(0)).unwrap();
-                            // c.write_all(&fbuffer[..received]).unwrap();
-                            let received_size = received as u64;
-                            // c.write_all(&buf[..received]).unwrap();
-                            loop {
-                                log::debug!("{}, {}", c.position(), received_size);
-                                if c.position() == received_size {
-                                    break;
-                                }
-                                c.read_exact(&mut buf[..4]).unwrap();
-                                let mlen = bytes2mlen(&buf);
-                                c.read_exact(&mut buf[4..mlen]).unwrap();
-                                log::debug!("record: {:?}", &buf[..mlen]);
-                                let fcode = bytes2fcode(&buf);
-                                if *fcode == 6 {
-                                    log::debug!("count: {}", count);
-                                    let h = bytes2header(&buf);
-                                    log::debug!("header: {:?}", h);
-                                    if count == 0 {
-                                        count = h.no;
-                                    } else {
-                                        count += 1
-                                    }
-                                    if count != h.no {
-                                        log::error!("count: {}, no: {}", count, h.no);
-                                        count = h.no;
-                                    }
-                                    let (n_match, n_bid, n_ask) = h.n_info();
-                                    let f6 = F6 {
-                                        header: h,
-                                        quote: bytes2quote(&buf[29..mlen], n_match, n_bid, n_ask),
-                                        // received: Local::now().to_rfc3339(),
-                                    };
-                                    sender.broadcast(f6);
-                                    // match sender.send(f6) {
-                                    //     Ok(_) => (),
-                                    //     Err(e) => println!("sender error: {:?}", e),
-                                    // }
-                                    // sender.send(f6).unwrap();
-                                    // rec_handler(&f6, count);
-                                }
-                            }
+                let received_at = RecvTime::now();
+                if !intake.filter.allows(rec_addr.ip()) {
+                    log::debug!("{}: dropped {} bytes from {}", market, received, rec_addr);
+                    continue;
+                }
+                // println!("{:?}", rec_addr);
+                // println!("received {} bytes {:?}", received, &fbuffer[..received]);
+                log::debug!("received {} bytes {:?}", received, &fbuffer[..received]);
+                let mut records = records_with(protocol, &fbuffer[..received]);
+                for raw in records.by_ref() {
+                    log::debug!("record: {:?}", raw);
+                    if intake.validator.check(raw).is_err() {
+                        continue;
+                    }
+                    if !handle(received_at, raw) {
+                        return;
+                    }
+                }
+                let stats = records.stats();
+                framer_stats += stats;
+                if stats.skipped_bytes > 0 {
+                    log::warn!(
+                        "{}: skipped {} bytes of {} in datagram from {}, {} bytes in {} resyncs so far",
+                        market,
+                        stats.skipped_bytes,
+                        received,
+                        rec_addr,
+                        framer_stats.skipped_bytes,
+                        framer_stats.resyncs
+                    );
+                }
+            }
+            Err(e) => match e.kind() {
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+                    if !idle() {
+                        return;
+                    }
+                }
+                _ => println!("recv function failed: {:?}", e),
+            },
+        }
+    }
+}
+
+// Decodes the records of one listener. TAIFEX records need the basic data seen before them
+// on the same channel, so each listener keeps its own decoder.
+enum Decoder<'a> {
+    Twse(&'a Registry),
+    Taifex(taifex::TaifexDecoder),
+}
+
+impl<'a> Decoder<'a> {
+    fn new(market: Market, registry: &'a Registry) -> Decoder<'a> {
+        match protocol_of(market) {
+            framer::Protocol::Taifex => Decoder::Taifex(taifex::TaifexDecoder::new()),
+            framer::Protocol::Twse => Decoder::Twse(registry),
+        }
+    }
+
+    fn decode(&mut self, raw: &[u8], market: Market) -> Option<Message> {
+        let decoded = match self {
+            Decoder::Taifex(decoder) => decoder.decode(raw),
+            Decoder::Twse(registry) => registry.decode(raw),
+        };
+        match decoded {
+            Ok(msg) => {
+                if msg.market() != Some(market) {
+                    log::warn!("{:?} record on the {} channel", msg.market(), market);
+                }
+                Some(msg)
+            }
+            Err(e) => {
+                log::warn!("skip record: {}", e);
+                None
+            }
+        }
+    }
+}
+
+// A record with the receive time of its datagram.
+type Received = (RecvTime, Vec<u8>);
+
+// Records of a stream held back while the gap before them is recovered. The first one showed
+// the gap and has been tracked, the others have not.
+struct Held {
+    gap: Gap,
+    since: Instant,
+    records: Vec<Received>,
+}
+
+// Forwards the records of one listener: decodes, captures and sends each one unless the
+// tracker has seen it already. A gap is queued for the recovery worker, if there is a peer, and the records of
+// its stream after it are held until the worker is done or `hold_timeout` passes, so that what
+// was recovered goes out in order.
+struct Forwarder<'a> {
+    market: Market,
+    decoder: Decoder<'a>,
+    sequencer: &'a Sequencer,
+    recovery: Option<RecoveryWorker>,
+    held: HashMap<StreamKey, Held>,
+    hold_timeout: Duration,
+    recorder: Option<RecorderHandle>,
+    sender: &'a Sender<Message>,
+}
+
+impl<'a> Forwarder<'a> {
+    fn new(
+        market: Market,
+        registry: &'a Registry,
+        intake: &Intake,
+        sequencer: &'a Sequencer,
+        sender: &'a Sender<Message>,
+    ) -> Forwarder<'a> {
+        Forwarder {
+            market,
+            decoder: Decoder::new(market, registry),
+            sequencer,
+            recovery: sequencer.recovery_worker(&intake.validator),
+            held: HashMap::new(),
+            // the worker may take `timeout` to connect and as long again for the reply
+            hold_timeout: sequencer
+                .peer
+                .as_ref()
+                .map_or(Duration::ZERO, |peer| peer.timeout() * 2),
+            recorder: intake.recorder.clone(),
+            sender,
+        }
+    }
+
+    // False once nobody receives anymore.
+    fn forward(&mut self, received: RecvTime, raw: &[u8]) -> bool {
+        let market = self.market;
+        let key = sequence(market, raw).map(|(key, _)| key);
+        if let Some(key) = key {
+            if let Some(held) = self.held.get_mut(&key) {
+                held.records.push((received, raw.to_vec()));
+                if held.records.len() <= MAX_PENDING {
+                    return true;
+                }
+                log::error!(
+                    "{}: {} records held, {:?} given up",
+                    market,
+                    MAX_PENDING,
+                    held.gap
+                );
+                return self.release(key, Vec::new());
+            }
+        }
+        match self.sequencer.tracker.track(market, raw) {
+            Some(SeqResult::Duplicate) => return true,
+            Some(SeqResult::Late) => {
+                log::warn!("{}: late record {:?}", market, sequence(market, raw))
+            }
+            Some(SeqResult::Reset(last)) => log::warn!(
+                "{}: numbering restarted at {:?} after {}",
+                market,
+                sequence(market, raw),
+                last
+            ),
+            Some(SeqResult::Gap(gap)) => {
+                log::error!(
+                    "{}: format {} missing {} to {}",
+                    market,
+                    gap.format,
+                    gap.from,
+                    gap.to
+                );
+                match key {
+                    Some(key) if self.request(&gap, key) => {
+                        let held = Held {
+                            gap,
+                            since: Instant::now(),
+                            records: vec![(received, raw.to_vec())],
+                        };
+                        self.held.insert(key, held);
+                        return true;
+                    }
+                    _ => {
+                        if self.sender.send(Message::Gap(gap)).is_err() {
+                            return false;
                         }
                     }
-                    IpAddr::V6(ref _rec_ip_v6) => (),
                 }
             }
-            Err(e) => println!("recv function failed: {:?}", e),
+            Some(SeqResult::InOrder) | None => (),
         }
+        self.send_record(received, raw)
+    }
+
+    fn send_record(&mut self, received: RecvTime, raw: &[u8]) -> bool {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(self.market, received, raw);
+        }
+        self.sequencer.journal.record(self.market, raw);
+        match self.decoder.decode(raw, self.market) {
+            Some(msg) => self.sender.send(msg).is_ok(),
+            None => true,
+        }
+    }
+
+    // Queues the gap for the recovery worker, false if it is not recovered.
+    fn request(&self, gap: &Gap, key: StreamKey) -> bool {
+        match &self.recovery {
+            Some(recovery) if recovery.request(gap.clone(), key) => true,
+            Some(_) => {
+                log::error!(
+                    "{}: recovery queue full, {:?} not recovered",
+                    self.market,
+                    gap
+                );
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn is_holding(&self) -> bool {
+        !self.held.is_empty()
+    }
+
+    // Releases the streams whose gap the recovery worker is done with, or that waited longer
+    // than `hold_timeout`.
+    fn poll(&mut self) -> bool {
+        let recovered: Vec<Recovered> = match &self.recovery {
+            Some(recovery) => recovery.recovered().try_iter().collect(),
+            None => return true,
+        };
+        for Recovered { gap, key, records } in recovered {
+            if self.held.get(&key).map(|held| &held.gap) != Some(&gap) {
+                log::warn!("{}: {:?} recovered after it was given up", self.market, gap);
+                continue;
+            }
+            if !self.release(key, records) {
+                return false;
+            }
+        }
+        let expired: Vec<StreamKey> = self
+            .held
+            .iter()
+            .filter(|(_, held)| held.since.elapsed() >= self.hold_timeout)
+            .map(|(&key, _)| key)
+            .collect();
+        for key in expired {
+            log::error!("{}: recovery for stream {:?} timed out", self.market, key);
+            if !self.release(key, Vec::new()) {
+                return false;
+            }
+        }
+        true
+    }
+
+    // Releases every stream, without waiting for the recovery worker.
+    fn flush(&mut self) -> bool {
+        while let Some(&key) = self.held.keys().next() {
+            if !self.release(key, Vec::new()) {
+                return false;
+            }
+        }
+        true
+    }
+
+    // Ends the hold on `key`: sends the records of its gap, recovered or late on the line, in
+    // order, with gap messages for the ranges still missing, then the records held after it.
+    fn release(&mut self, key: StreamKey, recovered: Vec<Vec<u8>>) -> bool {
+        let market = self.market;
+        let Held { gap, records, .. } = match self.held.remove(&key) {
+            Some(held) => held,
+            None => return true,
+        };
+        let in_gap = |raw: &[u8]| match sequence(market, raw) {
+            Some((k, seq)) if k == key && gap.from <= seq && seq <= gap.to => Some(seq),
+            _ => None,
+        };
+        let mut records = records.into_iter();
+        let first = records.next();
+        let (late, after): (Vec<Received>, Vec<Received>) =
+            records.partition(|(_, raw)| in_gap(raw).is_some());
+        let tracker = &self.sequencer.tracker;
+        // recovered records are stamped with the time they came back from the peer
+        let now = RecvTime::now();
+        let mut filled: Vec<(u64, Received)> = Vec::new();
+        for (received, raw) in recovered.into_iter().map(|raw| (now, raw)).chain(late) {
+            // the peer's records are checked like the line's, and each goes out once
+            if let Some(seq) = in_gap(&raw) {
+                if tracker.track(market, &raw) == Some(SeqResult::Late) {
+                    filled.push((seq, (received, raw)));
+                }
+            }
+        }
+        if !filled.is_empty() {
+            log::warn!("{}: filled {} of {:?}", market, filled.len(), gap);
+        }
+        filled.sort_by_key(|(seq, _)| *seq);
+        let mut missing = tracker
+            .missing(market, key, gap.from, gap.to)
+            .into_iter()
+            .peekable();
+        for (seq, (received, raw)) in filled {
+            while let Some((from, to)) = missing.next_if(|&(from, _)| from < seq) {
+                if !self.send_gap(&gap, from, to) {
+                    return false;
+                }
+            }
+            if !self.send_record(received, &raw) {
+                return false;
+            }
+        }
+        for (from, to) in missing {
+            if !self.send_gap(&gap, from, to) {
+                return false;
+            }
+        }
+        if let Some((received, raw)) = first {
+            if !self.send_record(received, &raw) {
+                return false;
+            }
+        }
+        after
+            .iter()
+            .all(|(received, raw)| self.forward(*received, raw))
+    }
+
+    fn send_gap(&self, gap: &Gap, from: u64, to: u64) -> bool {
+        let missing = Gap {
+            from,
+            to,
+            ..gap.clone()
+        };
+        self.sender.send(Message::Gap(missing)).is_ok()
+    }
+}
+
+/// Forwards the records of `market` received on `socket`. The records after a gap wait while
+/// it is recovered from the peer, then go out in order behind the recovered ones.
+pub fn process(
+    socket: UdpSocket,
+    market: Market,
+    sender: &Sender<Message>,
+    registry: &Registry,
+    intake: &Intake,
+    sequencer: &Sequencer,
+) {
+    let forwarder = Forwarder::new(market, registry, intake, sequencer, sender);
+    if forwarder.recovery.is_some() {
+        // a quiet feed must not keep recovered records waiting for the next datagram
+        if let Err(e) = socket.set_read_timeout(Some(RECOVERY_POLL)) {
+            log::error!("{}: cannot poll for recoveries: {}", market, e);
+        }
+    }
+    let forwarder = RefCell::new(forwarder);
+    let stop = |sent: bool| {
+        if !sent {
+            log::error!("{} listener stops: receiver gone", market);
+        }
+        sent
+    };
+    recv_records(
+        &socket,
+        market,
+        intake,
+        |received, raw| {
+            let mut forwarder = forwarder.borrow_mut();
+            stop(forwarder.poll() && forwarder.forward(received, raw))
+        },
+        || stop(forwarder.borrow_mut().poll()),
+    );
+}
+
+/// Listens on one of the redundant lines of `market` and passes its records undecoded to
+/// `arbitrate`, each with the receive time of its datagram.
+pub fn receive_line(
+    socket: UdpSocket,
+    market: Market,
+    line: Line,
+    sender: &Sender<(Line, RecvTime, Vec<u8>)>,
+    intake: &Intake,
+) {
+    recv_records(
+        &socket,
+        market,
+        intake,
+        |received, raw| {
+            if let Err(e) = sender.send((line, received, raw.to_vec())) {
+                log::error!("{} line {} stops: {}", market, line, e);
+                return false;
+            }
+            true
+        },
+        || true,
+    );
+}
+
+/// Merges the records of the A and B lines of `market`, forwarding each sequence number once
+/// and in order. A gap missing on both lines is given up on after `gap_timeout`. Records
+/// recovered from the peer are checked with the validator of `intake`, and its recorder
+/// captures the merged records, each once. Returns once both lines stop.
+pub fn arbitrate(
+    receiver: &Receiver<(Line, RecvTime, Vec<u8>)>,
+    market: Market,
+    sender: &Sender<Message>,
+    registry: &Registry,
+    intake: &Intake,
+    sequencer: &Sequencer,
+    gap_timeout: Duration,
+) {
+    let mut arbiter = Arbiter::new(MAX_PENDING);
+    let mut forwarder = Forwarder::new(market, registry, intake, sequencer, sender);
+    let mut out = Vec::new();
+    let mut gap_since: Option<Instant> = None;
+    let mut since_stats: u64 = 0;
+    let mut disconnected = false;
+    loop {
+        let mut timeout = match gap_since {
+            Some(since) => gap_timeout.saturating_sub(since.elapsed()),
+            None => gap_timeout,
+        };
+        if forwarder.is_holding() {
+            timeout = timeout.min(RECOVERY_POLL);
+        }
+        match receiver.recv_timeout(timeout) {
+            Ok((line, received, raw)) => match sequence(market, &raw) {
+                Some((key, seq)) => arbiter.push(line, key, seq, (received, raw), &mut out),
+                // no usable sequence number, nothing to arbitrate on
+                None => out.push((received, raw)),
+            },
+            Err(RecvTimeoutError::Timeout) => {
+                if gap_since.is_some() {
+                    arbiter.skip_gaps(&mut out);
+                    log_line_stats(market, &arbiter);
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                arbiter.skip_gaps(&mut out);
+                disconnected = true;
+            }
+        }
+        for (received, raw) in out.drain(..) {
+            since_stats += 1;
+            if !forwarder.forward(received, &raw) {
+                log::error!("{} arbiter stops: receiver gone", market);
+                return;
+            }
+            if since_stats == STATS_EVERY {
+                log_line_stats(market, &arbiter);
+                since_stats = 0;
+            }
+        }
+        // once both lines stop, nothing is held waiting for the peer anymore
+        if !(forwarder.poll() && (!disconnected || forwarder.flush())) {
+            log::error!("{} arbiter stops: receiver gone", market);
+            return;
+        }
+        if disconnected {
+            log_line_stats(market, &arbiter);
+            return;
+        }
+        gap_since = match arbiter.has_gap() {
+            true => gap_since.or_else(|| Some(Instant::now())),
+            false => None,
+        };
+    }
+}
+
+/// Logs the counts of every channel `tracker` follows, each `every` for as long as the process
+/// runs.
+pub fn report_sequence_stats(tracker: &SequenceTracker, every: Duration) {
+    loop {
+        thread::sleep(every);
+        for stats in tracker.stats() {
+            log::info!(
+                "{:?} format {}: in order {} dup {} late {} gaps {} missing {} resets {} last {}",
+                stats.market,
+                stats.format,
+                stats.in_order,
+                stats.duplicates,
+                stats.late,
+                stats.gaps,
+                stats.missing,
+                stats.resets,
+                stats.last
+            );
+        }
+    }
+}
+
+fn log_line_stats<T>(market: Market, arbiter: &Arbiter<T>) {
+    let (a, b) = (arbiter.stats(Line::A), arbiter.stats(Line::B));
+    log::warn!(
+        "{}: line A won {} lost {}, line B won {} lost {}, lost on both {}",
+        market,
+        a.won,
+        a.lost,
+        b.won,
+        b.lost,
+        arbiter.gap_lost()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::recorder::{FsyncPolicy, Recorder, RecorderConfig};
+    use crate::io::recovery::{serve, Journal, RecoveryClient, Request};
+    use crate::paser::checksum::{self, Validation};
+    use crate::paser::fixtures::F6_REC;
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use test_case::test_case;
+
+    #[test_case("10.3.0.1", vec![IpAddr::V4(Ipv4Addr::new(10, 3, 0, 1))]; "one")]
+    #[test_case(" 10.3.0.1, 10.4.0.1 ", vec![
+        IpAddr::V4(Ipv4Addr::new(10, 3, 0, 1)),
+        IpAddr::V4(Ipv4Addr::new(10, 4, 0, 1)),
+    ]; "dr site")]
+    #[test_case("any", vec![]; "any")]
+    #[test_case("", vec![]; "empty")]
+    fn source_filter_from_str_testcase(input: &str, expected: Vec<IpAddr>) {
+        assert_eq!(Ok(SourceFilter::new(expected)), input.parse());
+    }
+
+    #[test]
+    fn source_filter_test() {
+        let filter: SourceFilter = "10.3.0.1".parse().unwrap();
+        assert!(filter.allows(IpAddr::V4(Ipv4Addr::new(10, 3, 0, 1))));
+        assert!(!filter.allows(IpAddr::V4(Ipv4Addr::new(10, 3, 0, 2))));
+        assert!(SourceFilter::any().allows(IpAddr::V4(Ipv4Addr::new(10, 3, 0, 2))));
+        assert_eq!(
+            Err(String::from("invalid source address: 10.3.0")),
+            "10.3.0.1,10.3.0".parse::<SourceFilter>()
+        );
+    }
+
+    #[test_case("off", Ok(SourceMode::Off); "off")]
+    #[test_case("Userland", Ok(SourceMode::Userland); "userland")]
+    #[test_case("ssm", Ok(SourceMode::Ssm); "ssm")]
+    #[test_case("both", Ok(SourceMode::Both); "both")]
+    #[test_case("igmp", Err(String::from("unknown source mode: igmp")); "unknown")]
+    fn source_mode_from_str_testcase(input: &str, expected: Result<SourceMode, String>) {
+        assert_eq!(expected, input.parse::<SourceMode>());
+    }
+
+    fn loopback_roundtrip(port: u16, sources: &str) -> io::Result<usize> {
+        let group = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(232, 1, 1, 1)), port);
+        let interface = "127.0.0.1:0".parse().unwrap();
+        let socket = join_mcast_from(&group, &interface, &sources.parse().unwrap()).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender.set_multicast_loop_v4(true).unwrap();
+        sender.send_to(b"\x1b", group).unwrap();
+        let mut buf = [0u8; 16];
+        socket.recv_from(&mut buf).map(|(received, _)| received)
+    }
+
+    // format 6 record numbered `no`
+    fn f6_rec(no: u8) -> Vec<u8> {
+        let mut raw = F6_REC.to_vec();
+        raw[9] = no;
+        raw[38] = checksum::xor_checksum(&raw[1..38]);
+        raw
+    }
+
+    fn f6_nos(receiver: &Receiver<Message>) -> Vec<u64> {
+        receiver
+            .try_iter()
+            .map(|msg| match msg {
+                Message::F6(f6) => f6.header.no,
+                msg => panic!("not format 6: {:?}", msg),
+            })
+            .collect()
+    }
+
+    fn twse_intake() -> Intake {
+        Intake {
+            filter: SourceFilter::any(),
+            validator: Validator::new("twse", Validation::Strict),
+            recorder: None,
+        }
+    }
+
+    fn local_sequencer() -> Sequencer {
+        Sequencer::new(Arc::new(Journal::new(100)), None)
+    }
+
+    fn sent(receiver: &Receiver<Message>) -> Vec<String> {
+        receiver
+            .try_iter()
+            .map(|msg| match msg {
+                Message::F6(f6) => f6.header.no.to_string(),
+                Message::Gap(gap) => format!("gap {}-{}", gap.from, gap.to),
+                msg => panic!("not format 6: {:?}", msg),
+            })
+            .collect()
+    }
+
+    fn spawn_peer(journal: Journal) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let journal = Arc::new(journal);
+        std::thread::spawn(move || serve(listener, journal, "127.0.0.1".parse().unwrap()));
+        addr
+    }
+
+    #[test]
+    fn recover_from_peer_test() {
+        let registry = Registry::default();
+        let intake = twse_intake();
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        // the peer instance saw everything but 14, and has 13 corrupted
+        let journal = Journal::new(100);
+        for no in [0x11, 0x12, 0x13, 0x15] {
+            let mut raw = f6_rec(no);
+            if no == 0x13 {
+                raw[38] ^= 0xff;
+            }
+            journal.record(Market::Twse, &raw);
+        }
+        let client = RecoveryClient::new(spawn_peer(journal), Duration::from_secs(5));
+        let sequencer = Sequencer::new(Arc::new(Journal::new(100)), Some(client));
+        let mut forwarder = Forwarder::new(Market::Twse, &registry, &intake, &sequencer, &sender);
+        // 12 to 15 go to the recovery worker, 16 and what follows wait for it
+        for no in [0x11, 0x16, 0x14, 0x17] {
+            assert!(forwarder.forward(RecvTime::now(), &f6_rec(no)));
+        }
+        assert_eq!(vec!["11"], sent(&receiver));
+        let recovery = forwarder.recovery.as_ref().unwrap();
+        let recovered = recovery
+            .recovered()
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(2, recovered.records.len());
+        assert!(forwarder.release(recovered.key, recovered.records));
+        assert!(!forwarder.is_holding());
+        assert_eq!(
+            vec!["12", "gap 13-13", "14", "15", "16", "17"],
+            sent(&receiver)
+        );
+        let totals = sequencer.tracker.totals();
+        assert_eq!((1, 4, 3), (totals.gaps, totals.missing, totals.late));
+        // recovered records are journaled like the received ones
+        let request = Request {
+            market: Market::Twse,
+            key: (1, 6),
+            from: 11,
+            to: 17,
+        };
+        assert_eq!(6, sequencer.journal.range(&request).len());
+    }
+
+    #[test]
+    fn recovery_timeout_test() {
+        let registry = Registry::default();
+        let intake = twse_intake();
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let journal = Journal::new(100);
+        journal.record(Market::Twse, &f6_rec(0x12));
+        let client = RecoveryClient::new(spawn_peer(journal), Duration::from_secs(5));
+        let sequencer = Sequencer::new(Arc::new(Journal::new(100)), Some(client));
+        let mut forwarder = Forwarder::new(Market::Twse, &registry, &intake, &sequencer, &sender);
+        forwarder.hold_timeout = Duration::ZERO;
+        for no in [0x11, 0x14, 0x15] {
+            assert!(forwarder.forward(RecvTime::now(), &f6_rec(no)));
+        }
+        // given up on before the worker is back, what it brings later is dropped
+        assert!(forwarder.poll());
+        assert_eq!(vec!["11", "gap 12-13", "14", "15"], sent(&receiver));
+        let recovery = forwarder.recovery.as_ref().unwrap();
+        let recovered = recovery
+            .recovered()
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(1, recovered.records.len());
+        assert!(!forwarder.is_holding());
+        assert!(forwarder.release(recovered.key, recovered.records));
+        assert!(sent(&receiver).is_empty());
+    }
+
+    #[test]
+    fn process_quiet_feed_test() {
+        let journal = Journal::new(100);
+        for no in [0x12, 0x13] {
+            journal.record(Market::Twse, &f6_rec(no));
+        }
+        let client = RecoveryClient::new(spawn_peer(journal), Duration::from_secs(5));
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        std::thread::spawn(move || {
+            let intake = twse_intake();
+            let sequencer = Sequencer::new(Arc::new(Journal::new(100)), Some(client));
+            process(
+                socket,
+                Market::Twse,
+                &sender,
+                &Registry::default(),
+                &intake,
+                &sequencer,
+            );
+        });
+        let line = UdpSocket::bind("127.0.0.1:0").unwrap();
+        for no in [0x11, 0x14] {
+            line.send_to(&f6_rec(no), addr).unwrap();
+        }
+        // nothing follows 14 on the line, the recovered records still go out
+        let nos: Vec<String> = (0..4)
+            .map(
+                |_| match receiver.recv_timeout(Duration::from_secs(5)).unwrap() {
+                    Message::F6(f6) => f6.header.no.to_string(),
+                    msg => panic!("not format 6: {:?}", msg),
+                },
+            )
+            .collect();
+        assert_eq!(vec!["11", "12", "13", "14"], nos);
+    }
+
+    #[test]
+    fn gap_without_peer_test() {
+        let (registry, sequencer) = (Registry::default(), local_sequencer());
+        let intake = twse_intake();
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let mut forwarder = Forwarder::new(Market::Twse, &registry, &intake, &sequencer, &sender);
+        for no in [0x11, 0x14] {
+            assert!(forwarder.forward(RecvTime::now(), &f6_rec(no)));
+        }
+        assert!(!forwarder.is_holding());
+        assert_eq!(vec!["11", "gap 12-13", "14"], sent(&receiver));
+    }
+
+    #[test]
+    fn arbitrate_test() {
+        let (line_sender, line_receiver) = crossbeam_channel::unbounded();
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        for (line, no) in [
+            (Line::A, 0x11),
+            (Line::A, 0x13),
+            (Line::B, 0x11),
+            (Line::B, 0x12),
+            (Line::B, 0x13),
+        ] {
+            line_sender
+                .send((line, RecvTime::now(), f6_rec(no)))
+                .unwrap();
+        }
+        drop(line_sender);
+        let (registry, sequencer) = (Registry::default(), local_sequencer());
+        let intake = twse_intake();
+        let timeout = Duration::from_secs(5);
+        arbitrate(
+            &line_receiver,
+            Market::Twse,
+            &sender,
+            &registry,
+            &intake,
+            &sequencer,
+            timeout,
+        );
+        assert_eq!(vec![11, 12, 13], f6_nos(&receiver));
+        let totals = sequencer.tracker.totals();
+        assert_eq!((3, 0), (totals.in_order, totals.gaps));
+    }
+
+    #[test]
+    fn arbitrate_capture_test() {
+        let dir = std::env::temp_dir().join(format!("quote-arbitrate-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (recorder, join) = Recorder::new(RecorderConfig {
+            dir: dir.clone(),
+            sessions: Vec::new(),
+            max_bytes: 0,
+            index: false,
+            fsync: FsyncPolicy::Never,
+        })
+        .spawn();
+        let (line_sender, line_receiver) = crossbeam_channel::unbounded();
+        let (sender, _receiver) = crossbeam_channel::unbounded();
+        let received = RecvTime::from_nanos(1_639_962_000_000_000_000);
+        for (line, no) in [
+            (Line::A, 0x11),
+            (Line::B, 0x11),
+            (Line::B, 0x12),
+            (Line::A, 0x12),
+        ] {
+            line_sender.send((line, received, f6_rec(no))).unwrap();
+        }
+        drop(line_sender);
+        let (registry, sequencer) = (Registry::default(), local_sequencer());
+        let intake = Intake {
+            recorder: Some(recorder),
+            ..twse_intake()
+        };
+        arbitrate(
+            &line_receiver,
+            Market::Twse,
+            &sender,
+            &registry,
+            &intake,
+            &sequencer,
+            Duration::from_secs(5),
+        );
+        drop(intake);
+        join.join().unwrap();
+        // both lines carried both records, each is captured once and in order
+        let written: Vec<std::path::PathBuf> = std::fs::read_dir(&dir)
+            .unwrap()
+            .flat_map(|date| std::fs::read_dir(date.unwrap().path()).unwrap())
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(1, written.len());
+        assert_eq!(
+            [f6_rec(0x11), f6_rec(0x12)].concat(),
+            std::fs::read(&written[0]).unwrap()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn arbitrate_gap_timeout_test() {
+        let (line_sender, line_receiver) = crossbeam_channel::unbounded();
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let arbiter = std::thread::spawn(move || {
+            let (registry, sequencer) = (Registry::default(), local_sequencer());
+            let intake = twse_intake();
+            let timeout = Duration::from_millis(20);
+            arbitrate(
+                &line_receiver,
+                Market::Twse,
+                &sender,
+                &registry,
+                &intake,
+                &sequencer,
+                timeout,
+            );
+        });
+        line_sender
+            .send((Line::A, RecvTime::now(), f6_rec(0x11)))
+            .unwrap();
+        line_sender
+            .send((Line::A, RecvTime::now(), f6_rec(0x13)))
+            .unwrap();
+        // 12 never comes, 13 is let through once the gap times out with the lines still up
+        let next = || receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(next(), Message::F6(f6) if f6.header.no == 11));
+        assert_eq!(
+            Message::Gap(Gap {
+                market: Market::Twse,
+                format: String::from("6"),
+                from: 12,
+                to: 12,
+            }),
+            next()
+        );
+        assert!(matches!(next(), Message::F6(f6) if f6.header.no == 13));
+        drop(line_sender);
+        arbiter.join().unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs multicast on loopback"]
+    fn join_mcast_source_specific_test() {
+        assert_eq!(1, loopback_roundtrip(40101, "127.0.0.1").unwrap());
+        // the kernel drops senders that were not joined
+        assert!(loopback_roundtrip(40102, "127.0.0.2").is_err());
+    }
+
+    #[test]
+    fn join_mcast_from_v6_source_test() {
+        let group = "232.1.1.1:40103".parse().unwrap();
+        let interface = "127.0.0.1:0".parse().unwrap();
+        let sources = "::1".parse().unwrap();
+        let joined = join_mcast_from(&group, &interface, &sources);
+        assert_eq!(io::ErrorKind::InvalidInput, joined.unwrap_err().kind());
     }
 }
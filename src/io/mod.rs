@@ -1,11 +1,102 @@
+pub mod arbiter;
+pub mod framer;
 pub mod mcast;
+pub mod recorder;
+pub mod recovery;
 pub mod fs;
 pub mod redis;
 pub mod mqtt;
 // use crossbeam_channel::Receiver;
 use bus::BusReader as Receiver;
-use crate::paser::f6::F6;
+use crate::paser::message::Message;
+use crate::paser::price::{with_price_format, PriceFormat};
+use crate::paser::timestamp::{with_time_format, TimeFormat};
+use serde::Serialize;
+use std::str::FromStr;
+
+/// What the sinks publish: the decoded exchange records as they are, the normalized
+/// `MarketEvent`s under "<market>:<event>" keys, or both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    Raw,
+    Normalized,
+    Both,
+}
+
+impl OutputMode {
+    pub fn raw(self) -> bool {
+        self != OutputMode::Normalized
+    }
+
+    pub fn normalized(self) -> bool {
+        self != OutputMode::Raw
+    }
+}
+
+impl FromStr for OutputMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "raw" => Ok(OutputMode::Raw),
+            "normalized" => Ok(OutputMode::Normalized),
+            "both" => Ok(OutputMode::Both),
+            _ => Err(format!("unknown output mode: {}", s)),
+        }
+    }
+}
+
+/// How the sinks write the values whose format is configurable.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Formats {
+    pub price: PriceFormat,
+    pub time: TimeFormat,
+}
+
+impl Formats {
+    pub fn to_json<T: Serialize>(&self, value: &T) -> String {
+        with_price_format(self.price, || {
+            with_time_format(self.time, || serde_json::to_string(value).unwrap())
+        })
+    }
+}
 
 pub trait OutProcesser{
-    fn recv_f6_process(&mut self, receiver: &mut Receiver<F6>);
-}
\ No newline at end of file
+    fn recv_process(&mut self, receiver: &mut Receiver<Message>);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paser::price::Price;
+    use crate::paser::timestamp::ExchTime;
+    use test_case::test_case;
+
+    #[test_case("raw", Ok(OutputMode::Raw); "raw")]
+    #[test_case("Normalized", Ok(OutputMode::Normalized); "normalized")]
+    #[test_case("both", Ok(OutputMode::Both); "both")]
+    #[test_case("json", Err(String::from("unknown output mode: json")); "unknown")]
+    fn output_mode_from_str_testcase(input: &str, expected: Result<OutputMode, String>) {
+        assert_eq!(expected, input.parse::<OutputMode>());
+    }
+
+    #[test]
+    fn formats_to_json_test() {
+        let price = Price::from_scaled(18200);
+        assert_eq!("1.82", Formats::default().to_json(&price));
+        let formats = Formats {
+            price: PriceFormat::Str,
+            time: TimeFormat::Int,
+        };
+        assert_eq!("\"1.82\"", formats.to_json(&price));
+        let time = ExchTime::from_micros(32_400_140_866);
+        assert_eq!("\"09:00:00.140866\"", Formats::default().to_json(&time));
+        assert_eq!("32400140866", formats.to_json(&time));
+    }
+
+    #[test]
+    fn output_mode_test() {
+        assert_eq!((true, false), (OutputMode::Raw.raw(), OutputMode::Raw.normalized()));
+        assert_eq!((true, true), (OutputMode::Both.raw(), OutputMode::Both.normalized()));
+    }
+}
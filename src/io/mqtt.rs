@@ -1,9 +1,11 @@
 extern crate paho_mqtt as mqtt;
-use crate::io::OutProcesser;
-use crate::paser::f6::{F6Received, F6};
-use chrono::Local;
+use crate::io::{Formats, OutProcesser, OutputMode};
+use crate::paser::event::{MarketEvent, Normalizer};
+use crate::paser::message::{Message, Received};
+use crate::paser::timestamp::RecvTime;
 use crossbeam_channel::{Sender, Receiver, bounded};
 use bus::BusReader;
+use serde::Serialize;
 use std::thread;
 
 pub struct MqttOutProcesser {
@@ -12,14 +14,24 @@ pub struct MqttOutProcesser {
     username: String,
     password: String,
     // client: mqtt::AsyncClient,
-    sender: Sender<F6>,
-    // receiver: Receiver<F6>,
+    sender: Sender<Outgoing>,
+    // receiver: Receiver<Message>,
     threads: Vec<thread::JoinHandle<()>>,
+    mode: OutputMode,
+    normalizer: Normalizer,
+}
+
+/// Work for the publishing threads. Messages are normalized before they are handed out, so
+/// status changes are tracked in feed order.
+pub enum Outgoing {
+    Raw(Message),
+    Event(MarketEvent),
 }
 
 pub struct MqttWorker {
-    receiver: Receiver<F6>,
+    receiver: Receiver<Outgoing>,
     client: mqtt::AsyncClient,
+    formats: Formats,
 }
 
 
@@ -43,44 +55,51 @@ fn new_client(host: &str, clientid: &str, username: &str, password: &str) -> mqt
 }
 
 impl MqttWorker {
-    pub fn new(receiver: Receiver<F6>, host: &str, clientid: &str, username: &str, password: &str) -> MqttWorker{
+    pub fn new(receiver: Receiver<Outgoing>, host: &str, clientid: &str, username: &str, password: &str, formats: Formats) -> MqttWorker{
         let cli = new_client(host, clientid, username, password);
-        MqttWorker{receiver: receiver, client: cli}
+        MqttWorker{receiver: receiver, client: cli, formats}
     }
 
     pub fn start(&mut self) {
-        let mut count = 0;
         loop {    
-            let f6 = self.receiver.recv().unwrap();
-            let f6rec = F6Received {
-                f6: f6,
-                received: Local::now().to_rfc3339(),
+            let msg = match self.receiver.recv().unwrap() {
+                Outgoing::Raw(msg) => msg,
+                Outgoing::Event(event) => {
+                    self.publish(&event.market_key("/"), &event);
+                    continue;
+                }
             };
-            if count == 0 {
-                count = f6rec.f6.header.no;
-            } else {
-                count += 1;
-            }
-            if count != f6rec.f6.header.no {
-                log::error!("count: {}, no: {}", count, f6rec.f6.header.no);
-                count = f6rec.f6.header.no;
-            }
-            let f6_serialized = serde_json::to_string(&f6rec).unwrap();
-            let msg = mqtt::Message::new("f6", f6_serialized, 0);
-            let _tok = self.client.publish(msg);
-    
+            let rec = Received {
+                message: msg,
+                received: RecvTime::now(),
+            };
+            self.publish(&rec.message.market_key("/"), &rec);
         }
     }
+
+    fn publish<T: Serialize>(&self, topic: &str, rec: &T) {
+        let serialized = self.formats.to_json(rec);
+        let msg = mqtt::Message::new(topic, serialized, 0);
+        let _tok = self.client.publish(msg);
+    }
 }
 
 
 impl MqttOutProcesser {
-    pub fn new(host: &str, clientid: &str, username: &str, password: &str, n: usize) -> MqttOutProcesser {
+    pub fn new(
+        host: &str,
+        clientid: &str,
+        username: &str,
+        password: &str,
+        n: usize,
+        mode: OutputMode,
+        formats: Formats,
+    ) -> MqttOutProcesser {
         // let cli = new_client(host, clientid, username, password);
-        let (sender, receiver): (Sender<F6>, Receiver<F6>) = bounded(4096);
+        let (sender, receiver): (Sender<Outgoing>, Receiver<Outgoing>) = bounded(4096);
         let mut threads = Vec::with_capacity(n);
         for _ in 0..n {
-            let mut worker = MqttWorker::new(receiver.clone(), host, clientid, username, password);
+            let mut worker = MqttWorker::new(receiver.clone(), host, clientid, username, password, formats);
             let thread = thread::spawn(move || {
                worker.start()
             });
@@ -93,6 +112,8 @@ impl MqttOutProcesser {
             password: String::from(password),
             sender: sender,
             threads: threads,
+            mode,
+            normalizer: Normalizer::new(),
         }
     }
 
@@ -118,22 +139,20 @@ impl MqttOutProcesser {
 }
 
 impl OutProcesser for MqttOutProcesser {
-    fn recv_f6_process(&mut self, receiver: &mut BusReader<F6>) {
-        let mut count = 0;
+    fn recv_process(&mut self, receiver: &mut BusReader<Message>) {
         loop {
-            let f6 = receiver.recv().unwrap();
-            if count == 0 {
-                count = f6.header.no;
-            } else {
-                count += 1;
-            }
-            if count != f6.header.no {
-                log::error!("count: {}, no: {}", count, f6.header.no);
-                count = f6.header.no;
+            let msg = receiver.recv().unwrap();
+            if self.mode.normalized() {
+                for event in self.normalizer.normalize(&msg, RecvTime::now()) {
+                    if let Err(e) = self.sender.send(Outgoing::Event(event)) {
+                        log::error!("sender error: {:?}", e);
+                    }
+                }
             }
-            match self.sender.send(f6) {
-                Ok(_) => (),
-                Err(e) => println!("sender error: {:?}", e),
+            if self.mode.raw() {
+                if let Err(e) = self.sender.send(Outgoing::Raw(msg)) {
+                    log::error!("sender error: {:?}", e);
+                }
             }
         }
     }
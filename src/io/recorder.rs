@@ -0,0 +1,546 @@
+use crate::paser::market::Market;
+use crate::paser::sequence::{format_code, stream_key, StreamKey};
+use crate::paser::timestamp::{taipei, RecvTime};
+use chrono::{NaiveTime, TimeZone};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// how often buffered records are flushed to the files when the feed is quiet
+const FLUSH_EVERY: Duration = Duration::from_secs(1);
+// records waiting to be written, further ones are dropped rather than stall the listeners
+const MAX_QUEUED: usize = 65_536;
+// drops logged once per this many
+const LOG_DROPS_EVERY: u64 = 10_000;
+
+/// When the capture files are fsynced: never (left to the OS), after every `n` records, or
+/// once per interval. Parsed from "never", "always", "1000" or "500ms" / "2s".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FsyncPolicy {
+    Never,
+    Records(u64),
+    Interval(Duration),
+}
+
+impl FromStr for FsyncPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        let invalid = || format!("unknown fsync policy: {}", s);
+        match s.as_str() {
+            "never" | "off" => return Ok(FsyncPolicy::Never),
+            "always" => return Ok(FsyncPolicy::Records(1)),
+            _ => (),
+        }
+        if let Some(ms) = s.strip_suffix("ms") {
+            let ms = ms.parse().map_err(|_| invalid())?;
+            return Ok(FsyncPolicy::Interval(Duration::from_millis(ms)));
+        }
+        if let Some(secs) = s.strip_suffix('s') {
+            let secs = secs.parse().map_err(|_| invalid())?;
+            return Ok(FsyncPolicy::Interval(Duration::from_secs(secs)));
+        }
+        match s.parse() {
+            Ok(0) | Err(_) => Err(invalid()),
+            Ok(n) => Ok(FsyncPolicy::Records(n)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecorderConfig {
+    pub dir: PathBuf,
+    /// Session start times (+08:00). Files rotate at these and at midnight.
+    pub sessions: Vec<NaiveTime>,
+    /// A file rotates before growing past this many bytes, 0 for no limit.
+    pub max_bytes: u64,
+    /// Writes a receive-timestamp index next to every capture file.
+    pub index: bool,
+    pub fsync: FsyncPolicy,
+}
+
+/// A capture file name without extension, relative to the recorder directory, e.g.
+/// "20261018/twse_6_0830_000" for the TWSE format 6 records of the session from 08:30.
+fn capture_name(date: &str, start: &str, market: Market, key: StreamKey, part: u32) -> String {
+    format!(
+        "{}/{}_{}_{}_{:03}",
+        date,
+        market,
+        format_code(market, key),
+        start,
+        part
+    )
+}
+
+#[derive(Debug, PartialEq)]
+struct Session {
+    date: String,
+    start: String,
+    // nanoseconds since the epoch the next session starts at
+    until: i64,
+}
+
+fn session(sessions: &[NaiveTime], received: RecvTime) -> Session {
+    let local = received.to_datetime().naive_local();
+    let (date, time) = (local.date(), local.time());
+    let start = sessions
+        .iter()
+        .filter(|&&start| start <= time)
+        .max()
+        .copied()
+        .unwrap_or_else(|| NaiveTime::from_hms_opt(0, 0, 0).unwrap());
+    let until = match sessions.iter().filter(|&&start| start > time).min() {
+        Some(&next) => date.and_time(next),
+        None => date.and_hms_opt(0, 0, 0).unwrap() + chrono::Duration::days(1),
+    };
+    Session {
+        date: date.format("%Y%m%d").to_string(),
+        start: start.format("%H%M").to_string(),
+        until: RecvTime::from(taipei().from_local_datetime(&until).unwrap()).nanos(),
+    }
+}
+
+/// One entry of a capture index: when a record was received and where it starts in the
+/// capture file. Stored as two little-endian 8-byte integers.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct IndexEntry {
+    pub received: RecvTime,
+    pub offset: u64,
+}
+
+pub fn read_index(path: &Path) -> io::Result<Vec<IndexEntry>> {
+    let buffer = fs::read(path)?;
+    Ok(buffer
+        .chunks_exact(16)
+        .map(|entry| IndexEntry {
+            received: RecvTime::from_nanos(i64::from_le_bytes(entry[..8].try_into().unwrap())),
+            offset: u64::from_le_bytes(entry[8..].try_into().unwrap()),
+        })
+        .collect())
+}
+
+#[derive(Debug)]
+struct Capture {
+    part: u32,
+    bytes: u64,
+    file: BufWriter<File>,
+    index: Option<BufWriter<File>>,
+}
+
+impl Capture {
+    // appends to the last existing part, so a restart goes on where it stopped
+    fn open(
+        dir: &Path,
+        name: impl Fn(u32) -> String,
+        first: u32,
+        index: bool,
+    ) -> io::Result<Capture> {
+        let mut part = first;
+        while dir.join(format!("{}.new", name(part + 1))).exists() {
+            part += 1;
+        }
+        let path = dir.join(format!("{}.new", name(part)));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let append = |path: &Path| OpenOptions::new().create(true).append(true).open(path);
+        let file = append(&path)?;
+        let index = match index {
+            true => Some(BufWriter::new(append(&path.with_extension("idx"))?)),
+            false => None,
+        };
+        log::info!("recording to {}", path.display());
+        Ok(Capture {
+            part,
+            bytes: file.metadata()?.len(),
+            file: BufWriter::new(file),
+            index,
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        match &mut self.index {
+            Some(index) => index.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.file.get_ref().sync_data()?;
+        match &self.index {
+            Some(index) => index.get_ref().sync_data(),
+            None => Ok(()),
+        }
+    }
+}
+
+// a record handed to the recorder, stamped when it was
+#[derive(Debug)]
+struct Captured {
+    market: Market,
+    received: RecvTime,
+    raw: Vec<u8>,
+}
+
+/// Sending end of a running `Recorder`, cloned into the listeners. Never blocks: with the
+/// recorder behind by `MAX_QUEUED` records, further ones are dropped and counted.
+#[derive(Debug, Clone)]
+pub struct RecorderHandle {
+    sender: Sender<Captured>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl RecorderHandle {
+    /// Queues a record received at `received`, i.e. when its datagram came in.
+    pub fn record(&self, market: Market, received: RecvTime, raw: &[u8]) {
+        let captured = Captured {
+            market,
+            received,
+            raw: raw.to_vec(),
+        };
+        if let Err(e) = self.sender.try_send(captured) {
+            let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            if dropped % LOG_DROPS_EVERY == 1 {
+                log::error!(
+                    "{}: record not captured, {} dropped so far: {}",
+                    market,
+                    dropped,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Records dropped so far because the recorder was behind or stopped.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Writes raw records into one capture file per channel and session, the records simply
+/// concatenated like the exchange's `.new` files so `io::fs` can read them back.
+#[derive(Debug)]
+pub struct Recorder {
+    config: RecorderConfig,
+    session: Option<Session>,
+    files: HashMap<(Market, StreamKey), Capture>,
+    unsynced: u64,
+    last_sync: Instant,
+}
+
+impl Recorder {
+    pub fn new(config: RecorderConfig) -> Recorder {
+        Recorder {
+            config,
+            session: None,
+            files: HashMap::new(),
+            unsynced: 0,
+            last_sync: Instant::now(),
+        }
+    }
+
+    /// Runs the recorder on a thread of its own, which stops once every handle is dropped.
+    pub fn spawn(self) -> (RecorderHandle, thread::JoinHandle<()>) {
+        let (sender, receiver) = crossbeam_channel::bounded(MAX_QUEUED);
+        let join = thread::spawn(move || self.run(&receiver));
+        let dropped = Arc::new(AtomicU64::new(0));
+        (RecorderHandle { sender, dropped }, join)
+    }
+
+    fn run(mut self, receiver: &Receiver<Captured>) {
+        let tick = match self.config.fsync {
+            FsyncPolicy::Interval(interval) => interval.min(FLUSH_EVERY),
+            _ => FLUSH_EVERY,
+        };
+        loop {
+            let result = match receiver.recv_timeout(tick) {
+                Ok(captured) => self.write(captured.market, captured.received, &captured.raw),
+                Err(RecvTimeoutError::Timeout) => self.flush(),
+                Err(RecvTimeoutError::Disconnected) => {
+                    if let Err(e) = self.close() {
+                        log::error!("recorder: {}", e);
+                    }
+                    return;
+                }
+            };
+            let result = result.and_then(|_| match self.config.fsync {
+                FsyncPolicy::Interval(interval) if self.last_sync.elapsed() >= interval => {
+                    self.sync()
+                }
+                _ => Ok(()),
+            });
+            if let Err(e) = result {
+                log::error!("recorder: {}", e);
+            }
+        }
+    }
+
+    pub fn write(&mut self, market: Market, received: RecvTime, raw: &[u8]) -> io::Result<()> {
+        let key = match stream_key(market, raw) {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        let expired = match &self.session {
+            Some(session) => received.nanos() >= session.until,
+            None => true,
+        };
+        if expired {
+            self.close()?;
+            self.session = Some(session(&self.config.sessions, received));
+        }
+        let session = self.session.as_ref().unwrap();
+        let (dir, index) = (&self.config.dir, self.config.index);
+        let name = |part| capture_name(&session.date, &session.start, market, key, part);
+        let capture = match self.files.entry((market, key)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(Capture::open(dir, name, 0, index)?),
+        };
+        let max_bytes = self.config.max_bytes;
+        if max_bytes > 0 && capture.bytes > 0 && capture.bytes + raw.len() as u64 > max_bytes {
+            capture.sync()?;
+            *capture = Capture::open(dir, name, capture.part + 1, index)?;
+        }
+        if let Some(index) = &mut capture.index {
+            index.write_all(&received.nanos().to_le_bytes())?;
+            index.write_all(&capture.bytes.to_le_bytes())?;
+        }
+        capture.file.write_all(raw)?;
+        capture.bytes += raw.len() as u64;
+        self.unsynced += 1;
+        match self.config.fsync {
+            FsyncPolicy::Records(n) if self.unsynced >= n => self.sync(),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        for capture in self.files.values_mut() {
+            capture.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn sync(&mut self) -> io::Result<()> {
+        if self.unsynced > 0 {
+            for capture in self.files.values_mut() {
+                capture.sync()?;
+            }
+        }
+        self.unsynced = 0;
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    // writes out and closes the files of the current session
+    fn close(&mut self) -> io::Result<()> {
+        match self.config.fsync {
+            FsyncPolicy::Never => self.flush()?,
+            _ => self.sync()?,
+        }
+        self.files.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::framer::records;
+    use crate::io::fs::readf6file;
+    use crate::paser::checksum::{Validation, Validator};
+    use crate::paser::f6::F6;
+    use chrono::NaiveDate;
+    use test_case::test_case;
+
+    const CAPTURE: &str = "tests/data/f6_01000001_01001000_TP03.new";
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("quote-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn config(dir: &Path) -> RecorderConfig {
+        RecorderConfig {
+            dir: dir.to_path_buf(),
+            sessions: vec![
+                NaiveTime::from_hms_opt(8, 30, 0).unwrap(),
+                NaiveTime::from_hms_opt(15, 0, 0).unwrap(),
+            ],
+            max_bytes: 0,
+            index: false,
+            fsync: FsyncPolicy::Never,
+        }
+    }
+
+    fn at(hour: u32, min: u32) -> RecvTime {
+        let date = NaiveDate::from_ymd_opt(2026, 10, 18).unwrap();
+        RecvTime::from(
+            taipei()
+                .from_local_datetime(&date.and_hms_opt(hour, min, 0).unwrap())
+                .unwrap(),
+        )
+    }
+
+    fn listing(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir.join("20261018"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test_case("never", Ok(FsyncPolicy::Never); "never")]
+    #[test_case("always", Ok(FsyncPolicy::Records(1)); "always")]
+    #[test_case("1000", Ok(FsyncPolicy::Records(1000)); "records")]
+    #[test_case("500ms", Ok(FsyncPolicy::Interval(Duration::from_millis(500))); "millis")]
+    #[test_case("2s", Ok(FsyncPolicy::Interval(Duration::from_secs(2))); "secs")]
+    #[test_case("0", Err(String::from("unknown fsync policy: 0")); "zero")]
+    #[test_case("hourly", Err(String::from("unknown fsync policy: hourly")); "unknown")]
+    fn fsync_policy_from_str_testcase(input: &str, expected: Result<FsyncPolicy, String>) {
+        assert_eq!(expected, input.parse::<FsyncPolicy>());
+    }
+
+    #[test_case(at(8, 29), "20261018", "0000", at(8, 30); "before the first session")]
+    #[test_case(at(8, 30), "20261018", "0830", at(15, 0); "day session")]
+    #[test_case(at(23, 59), "20261018", "1500", RecvTime::from_nanos(at(0, 0).nanos() + 86_400_000_000_000); "night session")]
+    fn session_testcase(received: RecvTime, date: &str, start: &str, until: RecvTime) {
+        let sessions = config(Path::new(".")).sessions;
+        assert_eq!(
+            Session {
+                date: String::from(date),
+                start: String::from(start),
+                until: until.nanos(),
+            },
+            session(&sessions, received)
+        );
+    }
+
+    #[test]
+    fn recorder_readback_test() {
+        let dir = temp_dir("recorder-readback");
+        let mut recorder = Recorder::new(RecorderConfig {
+            index: true,
+            ..config(&dir)
+        });
+        let capture = fs::read(CAPTURE).unwrap();
+        let mut lens = Vec::new();
+        for (i, raw) in records(&capture).enumerate() {
+            let received = RecvTime::from_nanos(at(9, 0).nanos() + i as i64);
+            recorder.write(Market::Twse, received, raw).unwrap();
+            lens.push(raw.len() as u64);
+        }
+        recorder.close().unwrap();
+        let path = dir.join("20261018/twse_6_0830_000.new");
+        assert_eq!(capture, fs::read(&path).unwrap());
+
+        fn f6handler(_: F6) {}
+        let validator = Validator::new("test", Validation::Strict);
+        readf6file(&path, &validator, f6handler);
+        assert_eq!(
+            (1000, 0),
+            (validator.stats().checked(), validator.stats().corrupted())
+        );
+
+        let index = read_index(&path.with_extension("idx")).unwrap();
+        assert_eq!(1000, index.len());
+        assert_eq!(
+            IndexEntry {
+                received: RecvTime::from_nanos(at(9, 0).nanos() + 2),
+                offset: lens[0] + lens[1],
+            },
+            index[2]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recorder_rotation_test() {
+        let dir = temp_dir("recorder-rotation");
+        let capture = fs::read(CAPTURE).unwrap();
+        let raws: Vec<&[u8]> = records(&capture).take(5).collect();
+        let mut recorder = Recorder::new(RecorderConfig {
+            max_bytes: (raws[0].len() * 2) as u64,
+            fsync: FsyncPolicy::Records(2),
+            ..config(&dir)
+        });
+        for raw in raws.iter().take(3) {
+            recorder.write(Market::Twse, at(8, 29), raw).unwrap();
+        }
+        // a session boundary rotates too, and so does another channel
+        recorder.write(Market::Twse, at(8, 30), raws[3]).unwrap();
+        recorder.write(Market::Tpex, at(8, 30), raws[4]).unwrap();
+        recorder.close().unwrap();
+        assert_eq!(
+            vec![
+                "tpex_6_0830_000.new",
+                "twse_6_0000_000.new",
+                "twse_6_0000_001.new",
+                "twse_6_0830_000.new",
+            ],
+            listing(&dir)
+        );
+        let part = fs::read(dir.join("20261018/twse_6_0000_001.new")).unwrap();
+        assert_eq!(raws[2], &part[..]);
+
+        // a restart appends to the last part
+        let mut recorder = Recorder::new(RecorderConfig {
+            max_bytes: 0,
+            ..config(&dir)
+        });
+        recorder.write(Market::Twse, at(8, 29), raws[3]).unwrap();
+        recorder.close().unwrap();
+        let part = fs::read(dir.join("20261018/twse_6_0000_001.new")).unwrap();
+        assert_eq!([raws[2], raws[3]].concat(), part);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recorder_spawn_test() {
+        let dir = temp_dir("recorder-spawn");
+        let capture = fs::read(CAPTURE).unwrap();
+        let raw = records(&capture).next().unwrap();
+        let (handle, join) = Recorder::new(config(&dir)).spawn();
+        let received = RecvTime::from_nanos(1_639_962_000_000_000_000);
+        handle.record(Market::Twse, received, raw);
+        // too short for a channel
+        handle.record(Market::Twse, received, &raw[..2]);
+        drop(handle);
+        join.join().unwrap();
+        let written: Vec<PathBuf> = fs::read_dir(&dir)
+            .unwrap()
+            .flat_map(|date| fs::read_dir(date.unwrap().path()).unwrap())
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(1, written.len());
+        assert_eq!(raw, &fs::read(&written[0]).unwrap()[..]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recorder_handle_drops_test() {
+        // a recorder one record behind
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        let handle = RecorderHandle {
+            sender,
+            dropped: Arc::default(),
+        };
+        let received = RecvTime::from_nanos(1_639_962_000_000_000_000);
+        for _ in 0..3 {
+            handle.record(Market::Twse, received, &[0x1b]);
+        }
+        assert_eq!((1, 2), (receiver.len(), handle.dropped()));
+    }
+}
@@ -0,0 +1,452 @@
+use crate::io::mcast::SourceFilter;
+use crate::paser::checksum::Validator;
+use crate::paser::market::Market;
+use crate::paser::sequence::{is_reset, sequence, Gap, SequenceTracker, StreamKey};
+use crossbeam_channel::{Receiver, Sender};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// records served for a single request at most
+pub const MAX_RANGE: u64 = 10_000;
+// connections served at once, further ones are refused
+const MAX_CONNECTIONS: usize = 4;
+// a served connection idle this long is closed
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+// gaps waiting for the recovery worker of a listener
+const MAX_QUEUED: usize = 64;
+
+type Records = BTreeMap<u64, Vec<u8>>;
+
+/// The most recent `capacity` raw records of every channel by sequence number, kept for
+/// peers to recover their gaps from.
+#[derive(Debug)]
+pub struct Journal {
+    capacity: usize,
+    channels: Mutex<HashMap<(Market, StreamKey), Records>>,
+}
+
+impl Journal {
+    pub fn new(capacity: usize) -> Journal {
+        Journal {
+            capacity,
+            channels: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Keeps a framed record, unless it carries no sequence number.
+    pub fn record(&self, market: Market, raw: &[u8]) {
+        let (key, seq) = match sequence(market, raw) {
+            Some(sequenced) => sequenced,
+            None => return,
+        };
+        let mut channels = self.channels.lock().unwrap();
+        let records = channels.entry((market, key)).or_default();
+        // numbered anew, the old records would be kept over the new ones
+        if let Some((&last, _)) = records.last_key_value() {
+            if is_reset(last + 1, seq) {
+                records.clear();
+            }
+        }
+        records.insert(seq, raw.to_vec());
+        while records.len() > self.capacity {
+            records.pop_first();
+        }
+    }
+
+    /// Records of the range still kept, in order.
+    pub fn range(&self, request: &Request) -> Vec<Vec<u8>> {
+        let channels = self.channels.lock().unwrap();
+        match channels.get(&(request.market, request.key)) {
+            Some(records) => records
+                .range(request.from..=request.to)
+                .map(|(_, raw)| raw.clone())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Records `from` to `to`, both included, of one channel. Sent to a recovery server as a
+/// line "<market> <key> <key> <from> <to>", e.g. "twse 1 6 120 125".
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Request {
+    pub market: Market,
+    pub key: StreamKey,
+    pub from: u64,
+    pub to: u64,
+}
+
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            self.market, self.key.0, self.key.1, self.from, self.to
+        )
+    }
+}
+
+impl FromStr for Request {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let (market, key0, key1, from, to) = match fields[..] {
+            [market, key0, key1, from, to] => (market, key0, key1, from, to),
+            _ => return Err(format!("invalid recovery request: {}", s)),
+        };
+        let invalid =
+            |e: std::num::ParseIntError| format!("invalid recovery request: {}: {}", s, e);
+        let request = Request {
+            market: market.parse()?,
+            key: (
+                key0.parse().map_err(invalid)?,
+                key1.parse().map_err(invalid)?,
+            ),
+            from: from.parse().map_err(invalid)?,
+            to: to.parse().map_err(invalid)?,
+        };
+        if request.from > request.to || request.to - request.from >= MAX_RANGE {
+            return Err(format!("invalid recovery range: {}", s));
+        }
+        Ok(request)
+    }
+}
+
+/// Answers recovery requests from `journal` to the peers in `allowed`, a thread per connection
+/// and `MAX_CONNECTIONS` at once. Each request line is answered with the records found, every
+/// one prefixed by its length as a big-endian u16, followed by a zero length.
+pub fn serve(listener: TcpListener, journal: Arc<Journal>, allowed: SourceFilter) {
+    let open = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let accepted = stream.and_then(|stream| stream.peer_addr().map(|peer| (stream, peer)));
+        let (stream, peer) = match accepted {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                log::error!("recovery accept failed: {}", e);
+                continue;
+            }
+        };
+        if !allowed.allows(peer.ip()) {
+            log::warn!("recovery connection from {} refused", peer);
+            continue;
+        }
+        if open.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            open.fetch_sub(1, Ordering::SeqCst);
+            log::warn!("recovery connection from {} refused, {} open", peer, MAX_CONNECTIONS);
+            continue;
+        }
+        let (journal, open) = (journal.clone(), open.clone());
+        thread::spawn(move || {
+            if let Err(e) = answer(stream, &journal) {
+                log::warn!("recovery connection {} closed: {}", peer, e);
+            }
+            open.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+}
+
+fn answer(stream: TcpStream, journal: &Journal) -> io::Result<()> {
+    stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+    let mut writer = io::BufWriter::new(stream.try_clone()?);
+    for line in BufReader::new(stream).lines() {
+        let request: Request = line?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let records = journal.range(&request);
+        log::info!("recovery {}: {} records", request, records.len());
+        for raw in records.iter() {
+            writer.write_all(&(raw.len() as u16).to_be_bytes())?;
+            writer.write_all(raw)?;
+        }
+        writer.write_all(&[0, 0])?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Asks the recovery server of a peer instance for missing records.
+#[derive(Debug, Clone)]
+pub struct RecoveryClient {
+    peer: SocketAddr,
+    timeout: Duration,
+}
+
+impl RecoveryClient {
+    pub fn new(peer: SocketAddr, timeout: Duration) -> RecoveryClient {
+        RecoveryClient { peer, timeout }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// The records of the range the peer still has, in order. `timeout` bounds the connect
+    /// and every read and write.
+    pub fn fetch(&self, request: &Request) -> io::Result<Vec<Vec<u8>>> {
+        let mut stream = TcpStream::connect_timeout(&self.peer, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        stream.write_all(format!("{}\n", request).as_bytes())?;
+        let mut reader = BufReader::new(stream);
+        let mut records = Vec::new();
+        loop {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len)?;
+            let len = u16::from_be_bytes(len) as usize;
+            if len == 0 {
+                return Ok(records);
+            }
+            let mut raw = vec![0u8; len];
+            reader.read_exact(&mut raw)?;
+            records.push(raw);
+        }
+    }
+
+    /// The records of `gap` the peer has, none if it cannot be reached. A gap longer than a
+    /// request can ask for is recovered from its start.
+    pub fn recover(&self, gap: &Gap, key: StreamKey) -> Vec<Vec<u8>> {
+        let request = Request {
+            market: gap.market,
+            key,
+            from: gap.from,
+            to: gap.to.min(gap.from + MAX_RANGE - 1),
+        };
+        match self.fetch(&request) {
+            Ok(records) => records,
+            Err(e) => {
+                log::error!("recovery of {} from {} failed: {}", request, self.peer, e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// Records of a gap the peer had, in order and validated, sent back by a `RecoveryWorker`.
+#[derive(Debug, PartialEq)]
+pub struct Recovered {
+    pub gap: Gap,
+    pub key: StreamKey,
+    pub records: Vec<Vec<u8>>,
+}
+
+/// Recovers the gaps of one listener from the peer on a thread of its own, so the listener
+/// never waits on TCP. Gaps are queued with `request` and come back through `recovered`,
+/// for the listener to splice in. The thread stops once the worker is dropped.
+#[derive(Debug)]
+pub struct RecoveryWorker {
+    requests: Sender<(Gap, StreamKey)>,
+    recovered: Receiver<Recovered>,
+}
+
+impl RecoveryWorker {
+    /// Records from the peer are checked with `validator`, the one of the listener.
+    pub fn spawn(peer: RecoveryClient, validator: Validator) -> RecoveryWorker {
+        let (requests, queued) = crossbeam_channel::bounded::<(Gap, StreamKey)>(MAX_QUEUED);
+        let (done, recovered) = crossbeam_channel::bounded(MAX_QUEUED);
+        thread::spawn(move || {
+            for (gap, key) in queued {
+                let mut records = peer.recover(&gap, key);
+                records.retain(|raw| validator.check(raw).is_ok());
+                if done.send(Recovered { gap, key, records }).is_err() {
+                    return;
+                }
+            }
+        });
+        RecoveryWorker {
+            requests,
+            recovered,
+        }
+    }
+
+    /// Queues a gap, false if too many already wait and it is not recovered.
+    pub fn request(&self, gap: Gap, key: StreamKey) -> bool {
+        self.requests.try_send((gap, key)).is_ok()
+    }
+
+    pub fn recovered(&self) -> &Receiver<Recovered> {
+        &self.recovered
+    }
+}
+
+/// What the listeners share to keep the channels in sequence: the tracker, the journal of the
+/// records sent on and the peer gaps are recovered from, if any.
+#[derive(Debug)]
+pub struct Sequencer {
+    pub tracker: SequenceTracker,
+    pub journal: Arc<Journal>,
+    pub peer: Option<RecoveryClient>,
+}
+
+impl Sequencer {
+    pub fn new(journal: Arc<Journal>, peer: Option<RecoveryClient>) -> Sequencer {
+        Sequencer {
+            tracker: SequenceTracker::new(),
+            journal,
+            peer,
+        }
+    }
+
+    /// A recovery worker for a listener, none without a peer.
+    pub fn recovery_worker(&self, validator: &Validator) -> Option<RecoveryWorker> {
+        let peer = self.peer.clone()?;
+        Some(RecoveryWorker::spawn(peer, validator.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paser::checksum::Validation;
+    use test_case::test_case;
+
+    // F6 header up to the sequence number, enough for `sequence`
+    fn rec(no: u8) -> Vec<u8> {
+        vec![0x1b, 0x0, 0x41, 0x1, 0x6, 0x4, 0x0, 0x0, 0x0, no]
+    }
+
+    fn request(from: u64, to: u64) -> Request {
+        Request {
+            market: Market::Twse,
+            key: (1, 6),
+            from,
+            to,
+        }
+    }
+
+    #[test]
+    fn journal_test() {
+        let journal = Journal::new(3);
+        for no in [0x11, 0x12, 0x14, 0x15] {
+            journal.record(Market::Twse, &rec(no));
+        }
+        // 11 no longer kept, 13 never came
+        assert_eq!(vec![rec(0x12), rec(0x14)], journal.range(&request(11, 14)));
+        let tpex = Request {
+            market: Market::Tpex,
+            ..request(11, 14)
+        };
+        assert_eq!(Vec::<Vec<u8>>::new(), journal.range(&tpex));
+    }
+
+    #[test_case("twse 1 6 120 125", Ok(request(120, 125)); "twse")]
+    #[test_case("taifex 50 65 7 7", Ok(Request { market: Market::Taifex, key: (b'2', b'A'), from: 7, to: 7 }); "taifex")]
+    #[test_case("twse 1 6 120", Err(String::from("invalid recovery request: twse 1 6 120")); "missing field")]
+    #[test_case("nyse 1 6 1 2", Err(String::from("unknown market: nyse")); "unknown market")]
+    #[test_case("twse 1 6 125 120", Err(String::from("invalid recovery range: twse 1 6 125 120")); "reversed")]
+    #[test_case("twse 1 6 1 100000000", Err(String::from("invalid recovery range: twse 1 6 1 100000000")); "too long")]
+    fn request_from_str_testcase(input: &str, expected: Result<Request, String>) {
+        assert_eq!(expected, input.parse::<Request>());
+    }
+
+    #[test]
+    fn request_display_test() {
+        let request = request(120, 125);
+        assert_eq!(Ok(request), request.to_string().parse());
+    }
+
+    #[test]
+    fn fetch_test() {
+        let journal = Arc::new(Journal::new(100));
+        for no in [0x11, 0x12, 0x13, 0x15] {
+            journal.record(Market::Twse, &rec(no));
+        }
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || serve(listener, journal, "127.0.0.1".parse().unwrap()));
+        let client = RecoveryClient::new(addr, Duration::from_secs(5));
+        assert_eq!(
+            vec![rec(0x12), rec(0x13), rec(0x15)],
+            client.fetch(&request(12, 16)).unwrap()
+        );
+        assert_eq!(
+            Vec::<Vec<u8>>::new(),
+            client.fetch(&request(20, 30)).unwrap()
+        );
+    }
+
+    fn serving(journal: Journal, allowed: &str) -> RecoveryClient {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (journal, allowed) = (Arc::new(journal), allowed.parse().unwrap());
+        thread::spawn(move || serve(listener, journal, allowed));
+        RecoveryClient::new(addr, Duration::from_secs(5))
+    }
+
+    #[test]
+    fn serve_refused_test() {
+        let client = serving(Journal::new(10), "10.3.0.1");
+        assert!(client.fetch(&request(1, 2)).is_err());
+        // idle connections hold every slot, the next one is turned away
+        let client = serving(Journal::new(10), "127.0.0.1");
+        let idle: Vec<TcpStream> = (0..MAX_CONNECTIONS)
+            .map(|_| TcpStream::connect(client.peer).unwrap())
+            .collect();
+        assert!(client.fetch(&request(1, 2)).is_err());
+        drop(idle);
+    }
+
+    #[test]
+    fn journal_reset_test() {
+        let journal = Journal::new(3);
+        let mut last = rec(0x0);
+        last[6..10].copy_from_slice(&[0x0, 0x50, 0x0, 0x0]);
+        // 500000, then numbered anew from 1
+        for raw in [last, rec(0x1), rec(0x2)] {
+            journal.record(Market::Twse, &raw);
+        }
+        assert_eq!(vec![rec(0x1), rec(0x2)], journal.range(&request(1, 500_000)));
+    }
+
+    #[test]
+    fn recovery_worker_test() {
+        let journal = Journal::new(10);
+        for no in [0x11, 0x12, 0x13] {
+            journal.record(Market::Twse, &rec(no));
+        }
+        let client = serving(journal, "127.0.0.1");
+        // the records are not full, strict validation would reject them
+        let worker = RecoveryWorker::spawn(client, Validator::new("twse", Validation::Off));
+        let gap = Gap {
+            market: Market::Twse,
+            format: String::from("6"),
+            from: 12,
+            to: 14,
+        };
+        assert!(worker.request(gap.clone(), (1, 6)));
+        let recovered = worker.recovered().recv_timeout(Duration::from_secs(5));
+        assert_eq!(
+            Ok(Recovered {
+                gap,
+                key: (1, 6),
+                records: vec![rec(0x12), rec(0x13)],
+            }),
+            recovered
+        );
+    }
+
+    #[test]
+    fn recover_without_peer_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let client = RecoveryClient::new(addr, Duration::from_millis(200));
+        let gap = Gap {
+            market: Market::Twse,
+            format: String::from("6"),
+            from: 1,
+            to: 2,
+        };
+        assert_eq!(Vec::<Vec<u8>>::new(), client.recover(&gap, (1, 6)));
+        let sequencer = Sequencer::new(Arc::new(Journal::new(10)), None);
+        let validator = Validator::new("twse", Validation::Strict);
+        assert!(sequencer.recovery_worker(&validator).is_none());
+    }
+}
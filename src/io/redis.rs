@@ -1,22 +1,30 @@
-use crate::io::OutProcesser;
-use crate::paser::f6::{F6Received, F6};
-use chrono::Local;
+use crate::io::{Formats, OutProcesser, OutputMode};
+use crate::paser::event::Normalizer;
+use crate::paser::message::{Message, Received};
+use crate::paser::timestamp::RecvTime;
 // use crossbeam_channel::Receiver;
 use bus::BusReader as Receiver;
 use redis::{Client, Commands, Connection};
+use serde::Serialize;
 
 pub struct RedisOutProcesser {
     redis_uri: String,
     conn: Connection,
+    mode: OutputMode,
+    formats: Formats,
+    normalizer: Normalizer,
 }
 
 impl RedisOutProcesser {
-    pub fn new(redis_uri: &str) -> RedisOutProcesser {
+    pub fn new(redis_uri: &str, mode: OutputMode, formats: Formats) -> RedisOutProcesser {
         let client = Client::open(redis_uri.clone()).unwrap();
         let conn = client.get_connection().unwrap();
         RedisOutProcesser {
             redis_uri: String::from(redis_uri),
             conn: conn,
+            mode,
+            formats,
+            normalizer: Normalizer::new(),
         }
     }
 
@@ -24,22 +32,29 @@ impl RedisOutProcesser {
         let client = Client::open(self.redis_uri.clone()).unwrap();
         self.conn = client.get_connection().unwrap();
     }
-    fn push_f6(&mut self, key: &str, f6: F6Received) {
-        let f6_serialized = serde_json::to_string(&f6).unwrap();
-        // let f6_serialized = rmp_serde::to_vec(&f6).unwrap();
-        let _: () = self.conn.lpush(key, f6_serialized).unwrap();
+    fn push<T: Serialize>(&mut self, key: &str, rec: &T) {
+        let serialized = self.formats.to_json(rec);
+        // let serialized = rmp_serde::to_vec(rec).unwrap();
+        let _: () = self.conn.lpush(key, serialized).unwrap();
     }
 }
 
 impl OutProcesser for RedisOutProcesser {
-    fn recv_f6_process(&mut self, receiver: &mut Receiver<F6>) {
+    fn recv_process(&mut self, receiver: &mut Receiver<Message>) {
         loop {
-            let f6 = receiver.recv().unwrap();
-            let f6rec = F6Received {
-                f6: f6,
-                received: Local::now().to_rfc3339(),
+            let msg = receiver.recv().unwrap();
+            let rec = Received {
+                message: msg,
+                received: RecvTime::now(),
             };
-            self.push_f6("f6", f6rec);
+            if self.mode.normalized() {
+                for event in self.normalizer.normalize(&rec.message, rec.received) {
+                    self.push(&event.market_key(":"), &event);
+                }
+            }
+            if self.mode.raw() {
+                self.push(&rec.message.market_key(":"), &rec);
+            }
         }
     }
 }
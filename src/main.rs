@@ -1,15 +1,30 @@
 // use quote::paser::f6::bytes2f6;
 // use quote::io::fs::{readf6file, readf6filebuffer};
 use quote::io;
-use quote::io::mcast::{join_mcast, process};
-use quote::io::{OutProcesser};
-use quote::paser::f6::F6;
+use quote::io::arbiter::Line;
+use quote::io::mcast::{
+    arbitrate, join_mcast_from, process, receive_line, report_sequence_stats, Intake, SourceFilter,
+    SourceMode,
+};
+use quote::io::recorder::{FsyncPolicy, Recorder, RecorderConfig};
+use quote::io::recovery::{serve, Journal, RecoveryClient, Sequencer};
+use quote::io::{Formats, OutProcesser, OutputMode};
+use quote::paser::checksum::{Validation, Validator};
+use quote::paser::market::Market;
+use quote::paser::message::Message;
+use quote::paser::price::PriceFormat;
+use quote::paser::registry::Registry;
+use quote::paser::timestamp::TimeFormat;
 use quote::utils::{getenv, setup_log, str2ip};
-use std::net::SocketAddr;
+use chrono::NaiveTime;
+use std::env;
+use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::thread;
-// use std::path::Path;
+use std::time::Duration;
 // use std::sync::mpsc::{channel, Sender, Receiver};
-// use crossbeam_channel::{bounded, Receiver, Sender};
+use crossbeam_channel::{bounded, Receiver, Sender};
 use bus::Bus;
 
 #[macro_use]
@@ -17,37 +32,220 @@ extern crate lazy_static;
 
 lazy_static! {
     pub static ref MCAST_ADDR: SocketAddr = str2ip(&getenv("MCAST_GROUP", "224.0.100.100:10000"));
+    // no default for the other markets, only read when the market is enabled
+    pub static ref TPEX_MCAST_ADDR: SocketAddr =
+        str2ip(&env::var("TPEX_MCAST_GROUP").expect("TPEX_MCAST_GROUP is required for tpex"));
+    pub static ref TAIFEX_MCAST_ADDR: SocketAddr =
+        str2ip(&env::var("TAIFEX_MCAST_GROUP").expect("TAIFEX_MCAST_GROUP is required for taifex"));
+    // secondary line of the redundant feed, arbitrated with the primary when set
+    pub static ref MCAST_ADDR_B: Option<SocketAddr> = env::var("MCAST_GROUP_B").ok().map(|addr| str2ip(&addr));
+    pub static ref TPEX_MCAST_ADDR_B: Option<SocketAddr> =
+        env::var("TPEX_MCAST_GROUP_B").ok().map(|addr| str2ip(&addr));
+    pub static ref TAIFEX_MCAST_ADDR_B: Option<SocketAddr> =
+        env::var("TAIFEX_MCAST_GROUP_B").ok().map(|addr| str2ip(&addr));
+    pub static ref GAP_TIMEOUT: Duration =
+        Duration::from_millis(getenv("GAP_TIMEOUT_MS", "50").parse().unwrap());
+    pub static ref SEQUENCE_STATS_EVERY: Duration =
+        Duration::from_millis(getenv("SEQUENCE_STATS_MS", "60000").parse().unwrap());
+    // records kept per channel for peers to recover from
+    pub static ref JOURNAL_SIZE: usize = getenv("JOURNAL_SIZE", "100000").parse().unwrap();
+    // where this instance serves its journal, and the peer instance gaps are recovered from
+    pub static ref RECOVERY_ADDR: Option<SocketAddr> = env::var("RECOVERY_ADDR").ok().map(|addr| str2ip(&addr));
+    pub static ref RECOVERY_PEER: Option<SocketAddr> = env::var("RECOVERY_PEER").ok().map(|addr| str2ip(&addr));
+    // peers allowed to recover from this instance, only read when it serves its journal
+    pub static ref RECOVERY_ALLOW: SourceFilter = env::var("RECOVERY_ALLOW")
+        .expect("RECOVERY_ALLOW is required with RECOVERY_ADDR")
+        .parse()
+        .unwrap();
+    pub static ref RECOVERY_TIMEOUT: Duration =
+        Duration::from_millis(getenv("RECOVERY_TIMEOUT_MS", "200").parse().unwrap());
+    // capture files of the raw records, not written unless a directory is set
+    pub static ref RECORD_DIR: Option<PathBuf> = env::var("RECORD_DIR").ok().map(PathBuf::from);
+    pub static ref RECORD_SESSIONS: Vec<NaiveTime> = getenv("RECORD_SESSIONS", "08:30,15:00")
+        .split(',')
+        .filter(|start| !start.trim().is_empty())
+        .map(|start| NaiveTime::parse_from_str(start.trim(), "%H:%M").unwrap())
+        .collect();
+    pub static ref RECORD_MAX_BYTES: u64 = getenv("RECORD_MAX_BYTES", "0").parse().unwrap();
+    pub static ref RECORD_INDEX: bool = getenv("RECORD_INDEX", "false").parse().unwrap();
+    pub static ref RECORD_FSYNC: FsyncPolicy = getenv("RECORD_FSYNC", "1s").parse().unwrap();
+    // exchange senders, which differ between the production line and the DR site; each
+    // exchange has its own, "any" takes every sender
+    pub static ref TWSE_SOURCES: SourceFilter =
+        getenv("MCAST_SOURCES", "10.3.0.1").parse().unwrap();
+    pub static ref TPEX_SOURCES: SourceFilter = env::var("TPEX_MCAST_SOURCES")
+        .expect("TPEX_MCAST_SOURCES is required for tpex")
+        .parse()
+        .unwrap();
+    pub static ref TAIFEX_SOURCES: SourceFilter = env::var("TAIFEX_MCAST_SOURCES")
+        .expect("TAIFEX_MCAST_SOURCES is required for taifex")
+        .parse()
+        .unwrap();
+    pub static ref SOURCE_MODE: SourceMode = getenv("SOURCE_MODE", "userland").parse().unwrap();
     pub static ref MCAST_IF_ADDR: SocketAddr =
         str2ip(&getenv("MCAST_IF_ADDR", "192.168.32.23:10000"));
+    pub static ref MCAST_IF_ADDR_B: SocketAddr =
+        str2ip(&getenv("MCAST_IF_ADDR_B", &MCAST_IF_ADDR.to_string()));
     pub static ref REDIS_URI: String = getenv("REDIS_URI", "redis://127.0.0.1:6420/2");
     pub static ref MQTT_HOST: String = getenv("MQTT_HOST", "128.110.5.124:1884");
     pub static ref MQTT_USERNAME: String = getenv("MQTT_USERNAME", "yvictor");
     pub static ref MQTT_PASSWORD: String = getenv("MQTT_PASSWORD", "");
+    pub static ref VALIDATION: Validation = getenv("VALIDATION", "lenient").parse().unwrap();
+    pub static ref PRICE_FORMAT: PriceFormat = getenv("PRICE_FORMAT", "number").parse().unwrap();
+    pub static ref TIME_FORMAT: TimeFormat = getenv("TIME_FORMAT", "iso").parse().unwrap();
+    pub static ref OUTPUT_MODE: OutputMode = getenv("OUTPUT_MODE", "raw").parse().unwrap();
+    pub static ref MARKETS: Vec<Market> = getenv("MARKETS", "twse")
+        .split(',')
+        .map(|market| match market.trim().parse().unwrap() {
+            // see paser::taifex, none of its layouts has been checked against the spec yet
+            Market::Taifex => panic!("taifex is not supported until its layouts are verified"),
+            market => market,
+        })
+        .collect();
+}
+
+fn mcast_addr(market: Market) -> &'static SocketAddr {
+    match market {
+        Market::Twse => &MCAST_ADDR,
+        Market::Tpex => &TPEX_MCAST_ADDR,
+        Market::Taifex => &TAIFEX_MCAST_ADDR,
+    }
+}
+
+fn mcast_addr_b(market: Market) -> Option<&'static SocketAddr> {
+    match market {
+        Market::Twse => MCAST_ADDR_B.as_ref(),
+        Market::Tpex => TPEX_MCAST_ADDR_B.as_ref(),
+        Market::Taifex => TAIFEX_MCAST_ADDR_B.as_ref(),
+    }
+}
+
+fn mcast_sources(market: Market) -> &'static SourceFilter {
+    match market {
+        Market::Twse => &TWSE_SOURCES,
+        Market::Tpex => &TPEX_SOURCES,
+        Market::Taifex => &TAIFEX_SOURCES,
+    }
 }
 
 fn main() {
     setup_log();
-    // let (sender, receiver): (Sender<F6>, Receiver<F6>) = channel();
-    // let (sender, receiver): (Sender<F6>, Receiver<F6>) = bounded(4096);
-    let mut bus = Bus::<F6>::new(32768);
+    // let (sender, receiver): (Sender<Message>, Receiver<Message>) = channel();
+    // let (sender, receiver): (Sender<Message>, Receiver<Message>) = bounded(4096);
+    let formats = Formats {
+        price: *PRICE_FORMAT,
+        time: *TIME_FORMAT,
+    };
+    let mut bus = Bus::<Message>::new(32768);
     let mut receiver1 = bus.add_rx();
     let mut receiver2 = bus.add_rx();
 
-    let mut redis_outp = io::redis::RedisOutProcesser::new(&REDIS_URI);
-    let mut mqtt_outp =
-        io::mqtt::MqttOutProcesser::new(&MQTT_HOST, "rust_pub1", &MQTT_USERNAME, &MQTT_PASSWORD, 1);
-    let socket = join_mcast(&MCAST_ADDR, &MCAST_IF_ADDR).unwrap();
-    thread::spawn(move || redis_outp.recv_f6_process(&mut receiver2));
-    thread::spawn(move || mqtt_outp.recv_f6_process(&mut receiver1));
-    process(socket, &mut bus);
+    let mut redis_outp = io::redis::RedisOutProcesser::new(&REDIS_URI, *OUTPUT_MODE, formats);
+    let mut mqtt_outp = io::mqtt::MqttOutProcesser::new(
+        &MQTT_HOST,
+        "rust_pub1",
+        &MQTT_USERNAME,
+        &MQTT_PASSWORD,
+        1,
+        *OUTPUT_MODE,
+        formats,
+    );
+    thread::spawn(move || redis_outp.recv_process(&mut receiver2));
+    thread::spawn(move || mqtt_outp.recv_process(&mut receiver1));
+    // one listener per market, all feeding the bus through a single channel
+    let (sender, receiver): (Sender<Message>, Receiver<Message>) = bounded(32768);
+    let registry = Arc::new(Registry::default());
+    let journal = Arc::new(Journal::new(*JOURNAL_SIZE));
+    if let Some(addr) = *RECOVERY_ADDR {
+        let (listener, journal) = (TcpListener::bind(addr).unwrap(), journal.clone());
+        let allowed = RECOVERY_ALLOW.clone();
+        thread::spawn(move || serve(listener, journal, allowed));
+    }
+    let peer = RECOVERY_PEER.map(|peer| RecoveryClient::new(peer, *RECOVERY_TIMEOUT));
+    let sequencer = Arc::new(Sequencer::new(journal, peer));
+    let recorder = RECORD_DIR.as_ref().map(|dir| {
+        Recorder::new(RecorderConfig {
+            dir: dir.clone(),
+            sessions: RECORD_SESSIONS.clone(),
+            max_bytes: *RECORD_MAX_BYTES,
+            index: *RECORD_INDEX,
+            fsync: *RECORD_FSYNC,
+        })
+        .spawn()
+    });
+    let recording = recorder.as_ref().map(|(handle, _)| handle.clone());
+    let reported = sequencer.clone();
+    thread::spawn(move || report_sequence_stats(&reported.tracker, *SEQUENCE_STATS_EVERY));
+    let any = SourceFilter::any();
+    for &market in MARKETS.iter() {
+        let sources = mcast_sources(market);
+        let joined = if SOURCE_MODE.ssm() { sources } else { &any };
+        let filter = if SOURCE_MODE.userland() { sources } else { &any };
+        let socket = join_mcast_from(mcast_addr(market), &MCAST_IF_ADDR, joined).unwrap();
+        let (sender, registry, sequencer) = (sender.clone(), registry.clone(), sequencer.clone());
+        let addr_b = match mcast_addr_b(market) {
+            Some(addr_b) => addr_b,
+            None => {
+                let intake = Intake {
+                    filter: filter.clone(),
+                    validator: Validator::new(market.as_str(), *VALIDATION),
+                    recorder: recording.clone(),
+                };
+                thread::spawn(move || {
+                    process(socket, market, &sender, &registry, &intake, &sequencer)
+                });
+                continue;
+            }
+        };
+        // A and B lines of the same feed, merged by sequence number
+        let socket_b = join_mcast_from(addr_b, &MCAST_IF_ADDR_B, joined).unwrap();
+        let (line_sender, line_receiver) = bounded(32768);
+        for (line, socket) in [(Line::A, socket), (Line::B, socket_b)] {
+            let feed = format!("{} {}", market, line);
+            // captured once the lines are merged, see below
+            let intake = Intake {
+                filter: filter.clone(),
+                validator: Validator::new(&feed, *VALIDATION),
+                recorder: None,
+            };
+            let line_sender = line_sender.clone();
+            thread::spawn(move || receive_line(socket, market, line, &line_sender, &intake));
+        }
+        let intake = Intake {
+            filter: filter.clone(),
+            validator: Validator::new(market.as_str(), *VALIDATION),
+            recorder: recording.clone(),
+        };
+        thread::spawn(move || {
+            arbitrate(
+                &line_receiver,
+                market,
+                &sender,
+                &registry,
+                &intake,
+                &sequencer,
+                *GAP_TIMEOUT,
+            )
+        });
+    }
+    drop(sender);
+    drop(recording);
+    for msg in receiver {
+        bus.broadcast(msg);
+    }
+    // the listeners are gone, let the recorder flush what they queued
+    if let Some((handle, recorder)) = recorder {
+        drop(handle);
+        recorder.join().unwrap();
+    }
 
     // let path = Path::new("tests/data/f6_01000001_01001000_TP03.new");
     // // let path = Path::new("集中市場行情格式六_04000001_04500000_TP09.new");
     // let display = path.display();
     // log::info!("start parsing file: {}", display);
     // // readf6file(&Path::new("集中市場行情格式六_01000001_01500000_TP03.new"), f6handler);
-    // readf6file(&path, f6handler);
+    // readf6file(&path, &validator, f6handler);
     // log::info!("readf6file");
-    // readf6filebuffer(&path, f6handler);
+    // readf6filebuffer(&path, &validator, f6handler);
     // log::info!("finish");
 }
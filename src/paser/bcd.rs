@@ -1,3 +1,5 @@
+use crate::paser::price::Price;
+
 const BCD2STR: &[&str] = &[
     "00", "01", "02", "03", "04", "05", "06", "07", "08", "09", "10", "11", "12", "13", "14", "15",
     "16", "17", "18", "19", "20", "21", "22", "23", "24", "25", "26", "27", "28", "29", "30", "31",
@@ -18,6 +20,12 @@ const BCD2NUM: &[u64] = &[
     70, 71, 72, 73, 74, 75, 76, 77, 78, 79, 00, 00, 00, 00, 00, 00, 
     80, 81, 82, 83, 84, 85, 86, 87, 88, 89, 00, 00, 00, 00, 00, 00, 
     90, 91, 92, 93, 94, 95, 96, 97, 98, 99, 00, 00, 00, 00, 00, 00,
+    00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00,
+    00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00,
+    00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00,
+    00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00,
+    00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00,
+    00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00,
 ];
 
 pub fn bcd2str(packbcd: u8) -> &'static str {
@@ -25,6 +33,7 @@ pub fn bcd2str(packbcd: u8) -> &'static str {
     &BCD2STR[idx]
 }
 
+/// Any byte with a nibble above 9 decodes as 0; `try_bcdarr2num` rejects them instead.
 pub fn bcd2num(packbcd: u8) -> &'static u64 {
     &BCD2NUM[packbcd as usize]
 }
@@ -37,6 +46,46 @@ pub fn bcdarr2num(packbcd_arr: &[u8]) -> u64 {
     num
 }
 
+pub fn is_valid_bcd(packbcd: u8) -> bool {
+    (packbcd >> 4) < 10 && (packbcd & 0x0F) < 10
+}
+
+/// Like `bcdarr2num` but rejects nibbles above 9, returning the index of the first bad byte.
+pub fn try_bcdarr2num(packbcd_arr: &[u8]) -> Result<u64, usize> {
+    let mut num: u64 = 0;
+    for (idx, packbcd) in packbcd_arr.iter().enumerate() {
+        if !is_valid_bcd(*packbcd) {
+            return Err(idx);
+        }
+        num = num * 100 + bcd2num(*packbcd);
+    }
+    Ok(num)
+}
+
+pub fn num2bcd(num: u8) -> u8 {
+    ((num / 10) << 4) | (num % 10)
+}
+
+/// Inverse of `bcdarr2num`, fills `out` with the low `2 * out.len()` digits of `num`.
+pub fn num2bcdarr(num: u64, out: &mut [u8]) {
+    let mut num = num;
+    for packbcd in out.iter_mut().rev() {
+        *packbcd = num2bcd((num % 100) as u8);
+        num /= 100;
+    }
+}
+
+/// Inverse of `bcd2micros`.
+pub fn micros2bcd(micros: u64) -> [u8; 6] {
+    let secs = micros / 1_000_000;
+    let mut packbcd_arr = [0u8; 6];
+    num2bcdarr(secs / 3600, &mut packbcd_arr[0..1]);
+    num2bcdarr(secs / 60 % 60, &mut packbcd_arr[1..2]);
+    num2bcdarr(secs % 60, &mut packbcd_arr[2..3]);
+    num2bcdarr(micros % 1_000_000, &mut packbcd_arr[3..6]);
+    packbcd_arr
+}
+
 pub fn bcd2time(packbcd_arr: [u8; 6]) -> String {
     std::format!(
         "{}:{}:{}.{}{}{}",
@@ -49,9 +98,15 @@ pub fn bcd2time(packbcd_arr: [u8; 6]) -> String {
     )
 }
 
-pub fn bcd2price(packbcd_arr: [u8; 5]) -> f64 {
+/// HH MM SS and six fraction digits to microseconds since midnight.
+pub fn bcd2micros(packbcd_arr: [u8; 6]) -> u64 {
+    let hms = bcd2num(packbcd_arr[0]) * 3600 + bcd2num(packbcd_arr[1]) * 60 + bcd2num(packbcd_arr[2]);
+    hms * 1_000_000 + bcdarr2num(&packbcd_arr[3..])
+}
+
+pub fn bcd2price(packbcd_arr: [u8; 5]) -> Price {
     // 4ns -> 5ns when use ref
-    bcdarr2num(&packbcd_arr) as f64 / 10000.
+    Price::from_scaled(bcdarr2num(&packbcd_arr) as i64)
 }
 
 pub fn bcd2volume(packbcd_arr: [u8; 4]) -> u64 {
@@ -65,6 +120,7 @@ extern crate test_case;
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
     use test_case::test_case;
 
     #[test]
@@ -85,19 +141,58 @@ mod tests {
 
     #[test_case(18, 12; "0x12 == 18 -> 12")]
     #[test_case(128, 80; "0x80 == 128 -> 80")]
+    #[test_case(0x1a, 0; "low nibble a -> 0")]
+    #[test_case(0xff, 0; "0xff -> 0")]
     fn bcd2num_testcase(input: u8, expected: u64) {
         assert_eq!(expected, *bcd2num(input));
     }
 
+    #[test_case(0x99, true; "0x99 valid")]
+    #[test_case(0x00, true; "0x00 valid")]
+    #[test_case(0x1a, false; "low nibble a invalid")]
+    #[test_case(0xa1, false; "high nibble a invalid")]
+    fn is_valid_bcd_testcase(input: u8, expected: bool) {
+        assert_eq!(expected, is_valid_bcd(input));
+    }
+
+    #[test_case(&[0x1, 0x31], Ok(131); "0x01, 0x31 -> 131")]
+    #[test_case(&[0x1, 0x3f], Err(1); "0x01, 0x3f -> err at 1")]
+    #[test_case(&[0xff, 0x3f], Err(0); "0xff, 0x3f -> err at 0")]
+    fn try_bcdarr2num_testcase(input: &[u8], expected: Result<u64, usize>) {
+        assert_eq!(expected, try_bcdarr2num(input));
+    }
+
+    #[test_case([0x9, 0x0, 0x0, 0x14, 0x8, 0x66], 32_400_140_866; "09:00:00.140866")]
+    #[test_case([0x13, 0x30, 0x0, 0x0, 0x0, 0x0], 48_600_000_000; "13:30:00.000000")]
+    fn bcd2micros_testcase(input: [u8; 6], expected: u64) {
+        assert_eq!(expected, bcd2micros(input));
+        assert_eq!(input, micros2bcd(expected));
+    }
+
+    #[test_case(12, 0x12; "12 -> 0x12")]
+    #[test_case(80, 0x80; "80 -> 0x80")]
+    fn num2bcd_testcase(input: u8, expected: u8) {
+        assert_eq!(expected, num2bcd(input));
+    }
+
+    #[test_case(131, &[0x1, 0x31]; "131 -> 0x01, 0x31")]
+    #[test_case(852000, &[0x0, 0x0, 0x85, 0x20, 0x0]; "852000 -> 5 bytes")]
+    #[test_case(12345, &[0x45]; "keeps low digits")]
+    fn num2bcdarr_testcase(input: u64, expected: &[u8]) {
+        let mut out = vec![0xff; expected.len()];
+        num2bcdarr(input, &mut out);
+        assert_eq!(expected, &out[..]);
+    }
+
     #[test]
     fn bcd2price_test() {
-        assert_eq!(85.2, bcd2price([0, 0, 133, 32, 0]));
+        assert_eq!(Price::from_scaled(852000), bcd2price([0, 0, 133, 32, 0]));
     }
 
     #[test_case([0, 0, 133, 32, 0], 85.2; "0x0, 0x0, 0x85, 0x20, 0x0 -> 85.2")]
     #[test_case([0, 133, 32, 0, 0], 8520.; "0x0, 0x85, 0x20, 0x0, 0x00 -> 8520")]
     fn bcd2price_testcase(input: [u8; 5], expected: f64) {
-        assert_eq!(expected, bcd2price(input));
+        assert_eq!(expected, bcd2price(input).to_f64());
     }
 
     #[test]
@@ -110,4 +205,32 @@ mod tests {
     fn bcd2volume_testcase(input: [u8; 4], expected: u64) {
         assert_eq!(expected, bcd2volume(input));
     }
+
+    proptest! {
+        #[test]
+        fn try_bcdarr2num_proptest(input in prop::collection::vec(any::<u8>(), 0..10)) {
+            match try_bcdarr2num(&input) {
+                Ok(num) => {
+                    prop_assert!(input.iter().all(|b| is_valid_bcd(*b)));
+                    prop_assert_eq!(bcdarr2num(&input), num);
+                }
+                Err(idx) => {
+                    prop_assert!(!is_valid_bcd(input[idx]));
+                    prop_assert!(input[..idx].iter().all(|b| is_valid_bcd(*b)));
+                }
+            }
+        }
+
+        #[test]
+        fn num2bcdarr_roundtrip_proptest(num in 0..=9_999_999_999u64) {
+            let mut out = [0u8; 5];
+            num2bcdarr(num, &mut out);
+            prop_assert_eq!(Ok(num), try_bcdarr2num(&out));
+        }
+
+        #[test]
+        fn micros2bcd_roundtrip_proptest(micros in 0..86_400_000_000u64) {
+            prop_assert_eq!(micros, bcd2micros(micros2bcd(micros)));
+        }
+    }
 }
@@ -0,0 +1,157 @@
+use crate::paser::error::ParseError;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+pub const TERMINATOR: [u8; 2] = [0x0d, 0x0a];
+
+/// XOR of every byte between the ESC code and the checksum byte.
+pub fn xor_checksum(body: &[u8]) -> u8 {
+    body.iter().fold(0, |acc, b| acc ^ b)
+}
+
+/// Checks the checksum byte and the CR/LF terminator of a full `mlen`-sized record.
+pub fn validate_record(raw: &[u8]) -> Result<(), ParseError> {
+    let len = raw.len();
+    if len < 4 {
+        return Err(ParseError::Truncated { need: 4, got: len });
+    }
+    if raw[len - 2..] != TERMINATOR {
+        return Err(ParseError::Terminator([raw[len - 2], raw[len - 1]]));
+    }
+    let expected = xor_checksum(&raw[1..len - 3]);
+    if expected != raw[len - 3] {
+        return Err(ParseError::Checksum {
+            expected,
+            actual: raw[len - 3],
+        });
+    }
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Validation {
+    Off,
+    Lenient,
+    Strict,
+}
+
+impl FromStr for Validation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(Validation::Off),
+            "lenient" => Ok(Validation::Lenient),
+            "strict" => Ok(Validation::Strict),
+            _ => Err(format!("unknown validation mode: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ValidationStats {
+    checked: AtomicU64,
+    corrupted: AtomicU64,
+}
+
+impl ValidationStats {
+    pub fn checked(&self) -> u64 {
+        self.checked.load(Ordering::Relaxed)
+    }
+
+    pub fn corrupted(&self) -> u64 {
+        self.corrupted.load(Ordering::Relaxed)
+    }
+}
+
+/// Per-feed validation layer. `Lenient` counts and logs corrupted records but lets them
+/// through, `Strict` also rejects them.
+#[derive(Debug, Clone)]
+pub struct Validator {
+    feed: String,
+    mode: Validation,
+    stats: Arc<ValidationStats>,
+}
+
+impl Validator {
+    pub fn new(feed: &str, mode: Validation) -> Validator {
+        Validator {
+            feed: String::from(feed),
+            mode,
+            stats: Arc::new(ValidationStats::default()),
+        }
+    }
+
+    pub fn mode(&self) -> Validation {
+        self.mode
+    }
+
+    pub fn stats(&self) -> Arc<ValidationStats> {
+        self.stats.clone()
+    }
+
+    pub fn check(&self, raw: &[u8]) -> Result<(), ParseError> {
+        if self.mode == Validation::Off {
+            return Ok(());
+        }
+        self.stats.checked.fetch_add(1, Ordering::Relaxed);
+        match validate_record(raw) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let corrupted = self.stats.corrupted.fetch_add(1, Ordering::Relaxed) + 1;
+                log::warn!("{}: corrupted record #{}: {}", self.feed, corrupted, e);
+                match self.mode {
+                    Validation::Strict => Err(e),
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paser::fixtures::F1_REC;
+    use test_case::test_case;
+
+    fn corrupt(at: usize, byte: u8) -> Vec<u8> {
+        let mut raw = F1_REC.to_vec();
+        raw[at] = byte;
+        raw
+    }
+
+    #[test]
+    fn xor_checksum_test() {
+        assert_eq!(215, xor_checksum(&F1_REC[1..111]));
+    }
+
+    #[test_case(F1_REC.to_vec(), Ok(()); "valid")]
+    #[test_case(corrupt(111, 216), Err(ParseError::Checksum { expected: 215, actual: 216 }); "bad checksum")]
+    #[test_case(corrupt(10, 49), Err(ParseError::Checksum { expected: 214, actual: 215 }); "bad body")]
+    #[test_case(corrupt(113, 0), Err(ParseError::Terminator([13, 0])); "bad terminator")]
+    #[test_case(vec![27, 13, 10], Err(ParseError::Truncated { need: 4, got: 3 }); "truncated")]
+    fn validate_record_testcase(input: Vec<u8>, expected: Result<(), ParseError>) {
+        assert_eq!(expected, validate_record(&input));
+    }
+
+    #[test_case("strict", Ok(Validation::Strict); "strict")]
+    #[test_case("Lenient", Ok(Validation::Lenient); "lenient")]
+    #[test_case("off", Ok(Validation::Off); "off")]
+    #[test_case("loose", Err(String::from("unknown validation mode: loose")); "unknown")]
+    fn validation_from_str_testcase(input: &str, expected: Result<Validation, String>) {
+        assert_eq!(expected, input.parse::<Validation>());
+    }
+
+    #[test_case(Validation::Strict, true, 2, 1; "strict rejects")]
+    #[test_case(Validation::Lenient, false, 2, 1; "lenient passes")]
+    #[test_case(Validation::Off, false, 0, 0; "off skips")]
+    fn validator_check_testcase(mode: Validation, rejected: bool, checked: u64, corrupted: u64) {
+        let validator = Validator::new("test", mode);
+        assert_eq!(Ok(()), validator.check(F1_REC));
+        assert_eq!(rejected, validator.check(&corrupt(111, 0)).is_err());
+        let stats = validator.stats();
+        assert_eq!((checked, corrupted), (stats.checked(), stats.corrupted()));
+    }
+}
@@ -0,0 +1,193 @@
+use crate::paser::price::Price;
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+pub const MAX_DEPTH: usize = 5;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Level {
+    pub price: Price,
+    pub volume: u64,
+}
+
+impl Level {
+    pub fn new(price: Price, volume: u64) -> Level {
+        Level { price, volume }
+    }
+}
+
+/// Up to five price levels stored inline, best first. Only the levels actually sent are
+/// kept, so an absent level is never confused with a zero price.
+#[derive(Clone, Copy, Default)]
+pub struct Depth {
+    levels: [Level; MAX_DEPTH],
+    len: u8,
+}
+
+impl Depth {
+    pub fn new() -> Depth {
+        Depth::default()
+    }
+
+    /// Hands `level` back when all five levels are taken.
+    pub fn push(&mut self, level: Level) -> Result<(), Level> {
+        if self.len() == MAX_DEPTH {
+            return Err(level);
+        }
+        self.levels[self.len()] = level;
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, i: usize) -> Option<&Level> {
+        self.as_slice().get(i)
+    }
+
+    pub fn best(&self) -> Option<&Level> {
+        self.get(0)
+    }
+
+    pub fn as_slice(&self) -> &[Level] {
+        &self.levels[..self.len()]
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Level> {
+        self.as_slice().iter()
+    }
+}
+
+impl PartialEq for Depth {
+    fn eq(&self, other: &Depth) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Eq for Depth {}
+
+impl fmt::Debug for Depth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a> IntoIterator for &'a Depth {
+    type Item = &'a Level;
+    type IntoIter = std::slice::Iter<'a, Level>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl FromIterator<Level> for Depth {
+    /// Panics on more than five levels.
+    fn from_iter<I: IntoIterator<Item = Level>>(iter: I) -> Depth {
+        let mut depth = Depth::new();
+        for level in iter {
+            depth.push(level).expect("more than five levels");
+        }
+        depth
+    }
+}
+
+impl Serialize for Depth {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        for level in self {
+            seq.serialize_element(level)?;
+        }
+        seq.end()
+    }
+}
+
+struct DepthVisitor;
+
+impl<'de> Visitor<'de> for DepthVisitor {
+    type Value = Depth;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("at most 5 price levels")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Depth, A::Error> {
+        let mut depth = Depth::new();
+        while let Some(level) = seq.next_element()? {
+            depth
+                .push(level)
+                .map_err(|_| de::Error::invalid_length(MAX_DEPTH + 1, &self))?;
+        }
+        Ok(depth)
+    }
+}
+
+impl<'de> Deserialize<'de> for Depth {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Depth, D::Error> {
+        deserializer.deserialize_seq(DepthVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(price: f64, volume: u64) -> Level {
+        Level::new(Price::from_f64(price), volume)
+    }
+
+    #[test]
+    fn depth_test() {
+        let mut depth = Depth::new();
+        assert!(depth.is_empty());
+        assert_eq!(None, depth.best());
+        assert_eq!(Ok(()), depth.push(level(1.82, 6)));
+        assert_eq!(Ok(()), depth.push(level(1.81, 5)));
+        assert_eq!(2, depth.len());
+        assert_eq!(Some(&level(1.82, 6)), depth.best());
+        assert_eq!(None, depth.get(2));
+        assert_eq!(
+            vec![6, 5],
+            depth.iter().map(|l| l.volume).collect::<Vec<u64>>()
+        );
+    }
+
+    #[test]
+    fn depth_eq_ignores_absent_test() {
+        let mut depth: Depth = [level(1.82, 6), level(1.81, 5)].into_iter().collect();
+        let other: Depth = [level(1.82, 6)].into_iter().collect();
+        assert_ne!(depth, other);
+        depth = [level(1.82, 6)].into_iter().collect();
+        assert_eq!(depth, other);
+    }
+
+    #[test]
+    fn depth_push_full_test() {
+        let mut depth: Depth = (0..5).map(|i| level(1.0, i)).collect();
+        assert_eq!(Err(level(1.0, 5)), depth.push(level(1.0, 5)));
+        assert_eq!(5, depth.len());
+    }
+
+    #[test]
+    fn depth_serde_test() {
+        let depth: Depth = [level(1.82, 6), level(1.81, 5)].into_iter().collect();
+        let serialized = serde_json::to_string(&depth).unwrap();
+        assert_eq!(
+            r#"[{"price":1.82,"volume":6},{"price":1.81,"volume":5}]"#,
+            serialized
+        );
+        assert_eq!(depth, serde_json::from_str(&serialized).unwrap());
+        assert_eq!("[]", serde_json::to_string(&Depth::new()).unwrap());
+        let six = r#"[{"price":1,"volume":1},{"price":1,"volume":1},{"price":1,"volume":1},
+            {"price":1,"volume":1},{"price":1,"volume":1},{"price":1,"volume":1}]"#;
+        assert!(serde_json::from_str::<Depth>(six).is_err());
+    }
+}
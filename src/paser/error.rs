@@ -0,0 +1,85 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseError {
+    Truncated { need: usize, got: usize },
+    MissingEsc(u8),
+    InvalidBcd { offset: usize, byte: u8 },
+    LevelCount { n_match: u8, n_bid: u8, n_ask: u8 },
+    InvalidSymbol([u8; 6]),
+    LengthMismatch { mlen: usize, expected: usize },
+    Checksum { expected: u8, actual: u8 },
+    Terminator([u8; 2]),
+    UnsupportedVersion { fcode: u8, fver: u8 },
+    UnknownProduct(String),
+    InvalidDecimals { offset: usize, decimals: u8 },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Truncated { need, got } => {
+                write!(f, "truncated record: need {} bytes, got {}", need, got)
+            }
+            ParseError::MissingEsc(byte) => {
+                write!(f, "missing ESC 0x1b at record start, found {:#04x}", byte)
+            }
+            ParseError::InvalidBcd { offset, byte } => {
+                write!(f, "invalid packed BCD {:#04x} at offset {}", byte, offset)
+            }
+            ParseError::LevelCount { n_match, n_bid, n_ask } => write!(
+                f,
+                "level count out of range: n_match {}, n_bid {}, n_ask {}",
+                n_match, n_bid, n_ask
+            ),
+            ParseError::InvalidSymbol(symbol) => {
+                write!(f, "symbol is not valid UTF-8: {:?}", symbol)
+            }
+            ParseError::LengthMismatch { mlen, expected } => write!(
+                f,
+                "length mismatch: mlen {} but layout needs {}",
+                mlen, expected
+            ),
+            ParseError::Checksum { expected, actual } => write!(
+                f,
+                "checksum mismatch: computed {:#04x}, record has {:#04x}",
+                expected, actual
+            ),
+            ParseError::Terminator(term) => {
+                write!(f, "bad terminator: expected 0x0d 0x0a, found {:?}", term)
+            }
+            ParseError::UnsupportedVersion { fcode, fver } => {
+                write!(f, "unsupported version {} of format {}", fver, fcode)
+            }
+            ParseError::UnknownProduct(prod_id) => {
+                write!(f, "no basic data for product {:?}", prod_id)
+            }
+            ParseError::InvalidDecimals { offset, decimals } => write!(
+                f,
+                "decimal locator {} out of range at offset {}",
+                decimals, offset
+            ),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(ParseError::Truncated { need: 29, got: 4 }, "truncated record: need 29 bytes, got 4"; "truncated")]
+    #[test_case(ParseError::MissingEsc(0x30), "missing ESC 0x1b at record start, found 0x30"; "missing esc")]
+    #[test_case(ParseError::InvalidBcd { offset: 6, byte: 0x1a }, "invalid packed BCD 0x1a at offset 6"; "invalid bcd")]
+    #[test_case(ParseError::Checksum { expected: 0xd7, actual: 0xd6 }, "checksum mismatch: computed 0xd7, record has 0xd6"; "checksum")]
+    #[test_case(ParseError::LengthMismatch { mlen: 41, expected: 50 }, "length mismatch: mlen 41 but layout needs 50"; "length mismatch")]
+    #[test_case(ParseError::UnsupportedVersion { fcode: 6, fver: 5 }, "unsupported version 5 of format 6"; "unsupported version")]
+    #[test_case(ParseError::UnknownProduct(String::from("TXFA4")), "no basic data for product \"TXFA4\""; "unknown product")]
+    #[test_case(ParseError::InvalidDecimals { offset: 43, decimals: 23 }, "decimal locator 23 out of range at offset 43"; "invalid decimals")]
+    fn parse_error_display_testcase(err: ParseError, expected: &str) {
+        assert_eq!(expected, err.to_string())
+    }
+}
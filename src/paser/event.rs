@@ -0,0 +1,373 @@
+use crate::paser::depth::{Depth, Level};
+use crate::paser::f1::F1;
+use crate::paser::f6::{Status, F6};
+use crate::paser::market::Market;
+use crate::paser::message::Message;
+use crate::paser::price::Price;
+use crate::paser::taifex::{I020, I080};
+use crate::paser::timestamp::{ExchTime, RecvTime};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Trading phase of one symbol, as far as the venue tells it.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TradingPhase {
+    PreOpen,
+    Auction,
+    Continuous,
+    Delayed,
+    Closed,
+}
+
+impl From<Status> for TradingPhase {
+    fn from(status: Status) -> TradingPhase {
+        if status.closed {
+            TradingPhase::Closed
+        } else if status.delay_open || status.delay_close {
+            TradingPhase::Delayed
+        } else if status.auction {
+            TradingPhase::Auction
+        } else if status.opened {
+            TradingPhase::Continuous
+        } else {
+            TradingPhase::PreOpen
+        }
+    }
+}
+
+/// `indicative` marks simulated matching, e.g. before the open, that did not execute.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Trade {
+    pub price: Price,
+    pub volume: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_volume: Option<u64>,
+    pub indicative: bool,
+}
+
+/// Full snapshot of the disclosed depth, best level first. `implied_bid` and `implied_ask`
+/// are the best prices derived from spread orders, where the venue sends them.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct BookUpdate {
+    pub bids: Depth,
+    pub asks: Depth,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub implied_bid: Option<Level>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub implied_ask: Option<Level>,
+    pub indicative: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct StatusChange {
+    pub phase: TradingPhase,
+}
+
+/// Static data of a symbol for the session.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Reference {
+    pub name: String,
+    pub ref_price: Price,
+    pub limit_up: Price,
+    pub limit_down: Price,
+    pub lot_size: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    Trade(Trade),
+    BookUpdate(BookUpdate),
+    StatusChange(StatusChange),
+    Reference(Reference),
+}
+
+/// Venue-neutral market data event, e.g.
+/// `{"market":"twse","symbol":"2330","time":"...","received":"...","type":"trade",...}`.
+/// `time` is the exchange time of day, absent for data the venue does not stamp.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct MarketEvent {
+    pub market: Market,
+    pub symbol: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<ExchTime>,
+    pub received: RecvTime,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+impl MarketEvent {
+    /// Redis key / MQTT topic suffix of the event type.
+    pub fn key(&self) -> &'static str {
+        match self.kind {
+            EventKind::Trade(_) => "trade",
+            EventKind::BookUpdate(_) => "book",
+            EventKind::StatusChange(_) => "status",
+            EventKind::Reference(_) => "reference",
+        }
+    }
+
+    /// `key` prefixed with the market, e.g. "twse:trade" with `sep` ":".
+    pub fn market_key(&self, sep: &str) -> String {
+        format!("{}{}{}", self.market, sep, self.key())
+    }
+}
+
+/// Maps decoded messages to `MarketEvent`s. Status flags are repeated on every record, so
+/// the last phase of each symbol is kept and `StatusChange` only comes out when it moves.
+/// Messages without a mapping (indices, format 12, TAIFEX basic data, undecoded records,
+/// gaps) give no events.
+#[derive(Debug, Default)]
+pub struct Normalizer {
+    phases: HashMap<(Market, String), TradingPhase>,
+}
+
+impl Normalizer {
+    pub fn new() -> Normalizer {
+        Normalizer::default()
+    }
+
+    pub fn normalize(&mut self, msg: &Message, received: RecvTime) -> Vec<MarketEvent> {
+        let market = match msg.market() {
+            Some(market) => market,
+            None => return Vec::new(),
+        };
+        let mut events = Vec::new();
+        let mut push = |symbol: &str, time: Option<ExchTime>, kind: EventKind| {
+            events.push(MarketEvent {
+                market,
+                symbol: String::from(symbol),
+                time,
+                received,
+                kind,
+            })
+        };
+        match msg {
+            Message::F6(f6) => {
+                let symbol = f6.header.symbol();
+                let time = Some(f6.header.time());
+                let phase = TradingPhase::from(f6.header.status);
+                if self.phases.insert((market, symbol.to_string()), phase) != Some(phase) {
+                    push(
+                        symbol.as_str(),
+                        time,
+                        EventKind::StatusChange(StatusChange { phase }),
+                    );
+                }
+                for kind in f6_events(f6) {
+                    push(symbol.as_str(), time, kind);
+                }
+            }
+            Message::F1(f1) => push(f1.symbol.as_str(), None, f1_reference(f1)),
+            Message::I020(i020) => {
+                for kind in i020_trades(i020) {
+                    push(&i020.prod_id, Some(i020.match_time), kind);
+                }
+            }
+            Message::I080(i080) => push(&i080.prod_id, Some(i080.header.time), i080_book(i080)),
+            Message::F3(_)
+            | Message::F12(_)
+            | Message::I010(_)
+            | Message::Raw(_)
+            | Message::Gap(_) => (),
+        }
+        events
+    }
+}
+
+fn f6_events(f6: &F6) -> Vec<EventKind> {
+    let indicative = f6.header.status.simulation;
+    let mut events = Vec::new();
+    if let Some(trade) = f6.trade() {
+        events.push(EventKind::Trade(Trade {
+            price: trade.price,
+            volume: trade.volume,
+            // a trade implies volume, 0 means the version has no cumulative volume
+            total_volume: Some(f6.header.volsum()).filter(|&volsum| volsum > 0),
+            indicative,
+        }));
+    }
+    if !f6.header.trade_only {
+        events.push(EventKind::BookUpdate(BookUpdate {
+            bids: f6.quote.bidask.bid,
+            asks: f6.quote.bidask.ask,
+            implied_bid: None,
+            implied_ask: None,
+            indicative,
+        }));
+    }
+    events
+}
+
+fn f1_reference(f1: &F1) -> EventKind {
+    EventKind::Reference(Reference {
+        name: f1.name.clone(),
+        ref_price: f1.ref_price,
+        limit_up: f1.limit_up,
+        limit_down: f1.limit_down,
+        lot_size: f1.trading_unit,
+    })
+}
+
+fn i020_trades(i020: &I020) -> Vec<EventKind> {
+    std::iter::once(&i020.first)
+        .chain(i020.further.iter())
+        .map(|fill| {
+            EventKind::Trade(Trade {
+                price: fill.price,
+                volume: fill.volume,
+                total_volume: Some(i020.total_volume),
+                indicative: false,
+            })
+        })
+        .collect()
+}
+
+fn i080_book(i080: &I080) -> EventKind {
+    EventKind::BookUpdate(BookUpdate {
+        bids: i080.bidask.bid,
+        asks: i080.bidask.ask,
+        implied_bid: i080.derived.as_ref().map(|derived| derived.bid),
+        implied_ask: i080.derived.as_ref().map(|derived| derived.ask),
+        indicative: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paser::f1::bytes2f1;
+    use crate::paser::f6::bytes2f6;
+    use crate::paser::fixtures::{F1_REC, F6_TRADE_RAW, I020_RAW};
+    use crate::paser::taifex::{bytes2i020, DecimalLocators};
+    use test_case::test_case;
+
+    const RECEIVED: RecvTime = RecvTime::from_nanos(1_639_962_000_000_000_000);
+
+    fn with_status(raw: &[u8], st: u8) -> Message {
+        let mut raw = raw.to_vec();
+        raw[24] = st;
+        Message::F6(bytes2f6(&raw))
+    }
+
+    #[test_case(0x00, TradingPhase::PreOpen; "pre open")]
+    #[test_case(0x90, TradingPhase::Auction; "simulated auction")]
+    #[test_case(0x08, TradingPhase::Continuous; "opened")]
+    #[test_case(0x48, TradingPhase::Delayed; "delayed open")]
+    #[test_case(0x0c, TradingPhase::Closed; "closed")]
+    fn trading_phase_testcase(st: u8, expected: TradingPhase) {
+        assert_eq!(expected, TradingPhase::from(Status::from_byte(st)));
+    }
+
+    #[test]
+    fn normalize_f6_test() {
+        let mut normalizer = Normalizer::new();
+        let msg = Message::F6(bytes2f6(F6_TRADE_RAW));
+        let f6 = match &msg {
+            Message::F6(f6) => f6,
+            _ => unreachable!(),
+        };
+        let events = normalizer.normalize(&msg, RECEIVED);
+        let kinds: Vec<&str> = events.iter().map(MarketEvent::key).collect();
+        assert_eq!(vec!["status", "trade", "book"], kinds);
+        assert_eq!(
+            MarketEvent {
+                market: Market::Twse,
+                symbol: String::from("00632R"),
+                time: Some(f6.header.time()),
+                received: RECEIVED,
+                kind: EventKind::Trade(Trade {
+                    price: Price::from_f64(6.32),
+                    volume: 1,
+                    total_volume: None,
+                    indicative: true,
+                }),
+            },
+            events[1]
+        );
+        // the same phase again only repeats the trade and book
+        assert_eq!(2, normalizer.normalize(&msg, RECEIVED).len());
+        let closed = normalizer.normalize(&with_status(F6_TRADE_RAW, 0x0c), RECEIVED);
+        assert_eq!(
+            EventKind::StatusChange(StatusChange {
+                phase: TradingPhase::Closed
+            }),
+            closed[0].kind
+        );
+    }
+
+    #[test]
+    fn normalize_trade_only_test() {
+        let mut raw = F6_TRADE_RAW.to_vec();
+        raw[22] |= 0x01;
+        let events = Normalizer::new().normalize(&Message::F6(bytes2f6(&raw)), RECEIVED);
+        let kinds: Vec<&str> = events.iter().map(MarketEvent::key).collect();
+        assert_eq!(vec!["status", "trade"], kinds);
+    }
+
+    #[test]
+    fn normalize_f1_test() {
+        let events = Normalizer::new().normalize(&Message::F1(bytes2f1(F1_REC)), RECEIVED);
+        assert_eq!(
+            vec![MarketEvent {
+                market: Market::Twse,
+                symbol: String::from("0050"),
+                time: None,
+                received: RECEIVED,
+                kind: EventKind::Reference(Reference {
+                    name: String::from("元大台灣50"),
+                    ref_price: Price::from_f64(141.95),
+                    limit_up: Price::from_f64(156.1),
+                    limit_down: Price::from_f64(127.8),
+                    lot_size: 1000,
+                }),
+            }],
+            events
+        );
+    }
+
+    #[test]
+    fn normalize_i020_test() {
+        let mut locators = DecimalLocators::new();
+        locators.set("TXFA4", 0);
+        let i020 = bytes2i020(I020_RAW, &locators);
+        let events = Normalizer::new().normalize(&Message::I020(i020), RECEIVED);
+        let trades: Vec<(String, Option<u64>)> = events
+            .iter()
+            .map(|event| match &event.kind {
+                EventKind::Trade(trade) => (trade.price.to_string(), trade.total_volume),
+                kind => panic!("not a trade: {:?}", kind),
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (Price::from_f64(17650.).to_string(), Some(1234)),
+                (Price::from_f64(17651.).to_string(), Some(1234)),
+            ],
+            trades
+        );
+        assert!(events
+            .iter()
+            .all(|event| event.market == Market::Taifex && event.symbol == "TXFA4"));
+    }
+
+    #[test]
+    fn market_event_serde_test() {
+        let event = MarketEvent {
+            market: Market::Tpex,
+            symbol: String::from("6488"),
+            time: None,
+            received: RECEIVED,
+            kind: EventKind::StatusChange(StatusChange {
+                phase: TradingPhase::Continuous,
+            }),
+        };
+        let serialized = serde_json::to_value(&event).unwrap();
+        assert_eq!("status_change", serialized["type"]);
+        assert_eq!("continuous", serialized["phase"]);
+        assert_eq!(None, serialized.get("time"));
+        assert_eq!("tpex/status", event.market_key("/"));
+        assert_eq!(event, serde_json::from_value(serialized).unwrap());
+    }
+}
@@ -0,0 +1,205 @@
+use crate::paser::error::ParseError;
+use crate::paser::f6::{bcd_at, check_len, ESC};
+use crate::paser::market::Market;
+use crate::paser::price::Price;
+use crate::paser::symbol::Symbol;
+use encoding_rs::BIG5;
+use serde::{Deserialize, Serialize};
+
+// format 1 version 9 is a fixed 114-byte record including checksum and CR/LF
+pub const F1_LEN: usize = 114;
+
+/// Format 1 stock basic data, published once per symbol before the session opens.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct F1 {
+    pub mlen: u16,
+    cate: u8,
+    fcode: u8,
+    fver: u8,
+    pub no: u64,
+    pub symbol: Symbol,
+    pub name: String,
+    pub industry: String,
+    pub security_type: String,
+    stock_remark: String,
+    anomaly_code: u8,
+    board_remark: char,
+    pub ref_price: Price,
+    pub limit_up: Price,
+    pub limit_down: Price,
+    non_ten_par: bool,
+    abnormal_recommend: bool,
+    special_abnormal: bool,
+    day_trade: char,
+    short_sell_exempt: bool,
+    sbl_short_sell_exempt: bool,
+    match_interval: u64,
+    warrant: bool,
+    strike_price: Price,
+    outstanding: u64,
+    expiry: u64,
+    foreign: bool,
+    pub trading_unit: u64,
+    currency: String,
+    line: u8,
+}
+
+fn ascii_at(raw: &[u8], start: usize, end: usize) -> String {
+    String::from_utf8_lossy(&raw[start..end]).trim_end().to_string()
+}
+
+pub fn bytes2f1(raw: &[u8]) -> F1 {
+    try_bytes2f1(raw).unwrap()
+}
+
+pub fn try_bytes2f1(raw: &[u8]) -> Result<F1, ParseError> {
+    check_len(raw, F1_LEN)?;
+    if raw[0] != ESC {
+        return Err(ParseError::MissingEsc(raw[0]));
+    }
+    let mlen = bcd_at(raw, 1, 3)? as usize;
+    if mlen != F1_LEN {
+        return Err(ParseError::LengthMismatch {
+            mlen,
+            expected: F1_LEN,
+        });
+    }
+    let symbol = Symbol::from_bytes(raw[10..16].try_into().unwrap())?;
+    let (name, _, _) = BIG5.decode(&raw[16..32]);
+    let price = |start: usize| -> Result<Price, ParseError> {
+        Ok(Price::from_scaled(bcd_at(raw, start, start + 5)? as i64))
+    };
+    Ok(F1 {
+        mlen: mlen as u16,
+        cate: bcd_at(raw, 3, 4)? as u8,
+        fcode: bcd_at(raw, 4, 5)? as u8,
+        fver: bcd_at(raw, 5, 6)? as u8,
+        no: bcd_at(raw, 6, 10)?,
+        symbol,
+        name: name.trim_end().to_string(),
+        industry: ascii_at(raw, 32, 34),
+        security_type: ascii_at(raw, 34, 36),
+        stock_remark: ascii_at(raw, 36, 38),
+        anomaly_code: bcd_at(raw, 38, 39)? as u8,
+        board_remark: raw[39] as char,
+        ref_price: price(40)?,
+        limit_up: price(45)?,
+        limit_down: price(50)?,
+        non_ten_par: raw[55] != b' ',
+        abnormal_recommend: raw[56] != b' ',
+        special_abnormal: raw[57] != b' ',
+        day_trade: raw[58] as char,
+        short_sell_exempt: raw[59] == b'Y',
+        sbl_short_sell_exempt: raw[60] == b'Y',
+        match_interval: bcd_at(raw, 61, 64)?,
+        warrant: raw[64] == b'Y',
+        strike_price: price(65)?,
+        outstanding: bcd_at(raw, 80, 85)?,
+        expiry: bcd_at(raw, 99, 103)?,
+        foreign: raw[103] != b' ',
+        trading_unit: bcd_at(raw, 104, 107)?,
+        currency: ascii_at(raw, 107, 110),
+        line: bcd_at(raw, 110, 111)? as u8,
+    })
+}
+
+impl F1 {
+    pub fn market(&self) -> Option<Market> {
+        Market::from_cate(self.cate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paser::fixtures::F1_REC;
+    use test_case::test_case;
+
+    #[test]
+    fn bytes2f1_test() {
+        assert_eq!(
+            bytes2f1(F1_REC),
+            F1 {
+                mlen: 114,
+                cate: 1,
+                fcode: 1,
+                fver: 9,
+                no: 1,
+                symbol: "0050".parse().unwrap(),
+                name: String::from("元大台灣50"),
+                industry: String::from("00"),
+                security_type: String::from(""),
+                stock_remark: String::from(""),
+                anomaly_code: 0,
+                board_remark: '0',
+                ref_price: Price::from_f64(141.95),
+                limit_up: Price::from_f64(156.1),
+                limit_down: Price::from_f64(127.8),
+                non_ten_par: false,
+                abnormal_recommend: false,
+                special_abnormal: false,
+                day_trade: 'A',
+                short_sell_exempt: true,
+                sbl_short_sell_exempt: true,
+                match_interval: 0,
+                warrant: false,
+                strike_price: Price::ZERO,
+                outstanding: 0,
+                expiry: 0,
+                foreign: false,
+                trading_unit: 1000,
+                currency: String::from(""),
+                line: 1,
+            }
+        )
+    }
+
+    #[test_case(&[
+        27, 1, 20, 1, 1, 9, 0, 0, 0, 1, 48, 51, 52, 51, 48, 80, 184, 85, 174, 252, 164, 164, 171, 72,
+        49, 55, 176, 226, 48, 49, 32, 32, 48, 48, 87, 52, 32, 32, 0, 48, 0, 0, 1, 8, 0, 0, 0, 1, 84,
+        0, 0, 0, 0, 98, 0, 32, 32, 32, 32, 32, 32, 0, 0, 0, 89, 0, 1, 152, 136, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 80, 0, 0, 0, 34, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 32, 34, 7, 18, 32, 0,
+        16, 0, 32, 32, 32, 2, 34, 13, 10
+    ], F1 {
+        mlen: 114,
+        cate: 1,
+        fcode: 1,
+        fver: 9,
+        no: 1,
+        symbol: "03430P".parse().unwrap(),
+        name: String::from("萬海中信17售01"),
+        industry: String::from("00"),
+        security_type: String::from("W4"),
+        stock_remark: String::from(""),
+        anomaly_code: 0,
+        board_remark: '0',
+        ref_price: Price::from_f64(1.08),
+        limit_up: Price::from_f64(1.54),
+        limit_down: Price::from_f64(0.62),
+        non_ten_par: false,
+        abnormal_recommend: false,
+        special_abnormal: false,
+        day_trade: ' ',
+        short_sell_exempt: false,
+        sbl_short_sell_exempt: false,
+        match_interval: 0,
+        warrant: true,
+        strike_price: Price::from_f64(198.88),
+        outstanding: 5000,
+        expiry: 20220712,
+        foreign: false,
+        trading_unit: 1000,
+        currency: String::from(""),
+        line: 2,
+    }; "case warrant")]
+    fn bytes2f1_testcase(input: &[u8], expected: F1) {
+        assert_eq!(expected, bytes2f1(input))
+    }
+
+    #[test_case(F1_REC[..100].to_vec(), ParseError::Truncated { need: 114, got: 100 }; "truncated")]
+    #[test_case([&[0x1b, 0x1, 0x31], &F1_REC[3..]].concat(), ParseError::LengthMismatch { mlen: 131, expected: 114 }; "length mismatch")]
+    #[test_case([&F1_REC[..40], &[0x0a], &F1_REC[41..]].concat(), ParseError::InvalidBcd { offset: 40, byte: 0x0a }; "invalid price")]
+    fn try_bytes2f1_error_testcase(input: Vec<u8>, expected: ParseError) {
+        assert_eq!(Err(expected), try_bytes2f1(&input))
+    }
+}
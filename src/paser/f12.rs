@@ -0,0 +1,155 @@
+use crate::paser::error::ParseError;
+use crate::paser::f6::{bcd_at, check_len, ESC, TRAILER_LEN};
+use crate::paser::market::Market;
+use crate::paser::price::Price;
+use crate::paser::symbol::Symbol;
+use crate::paser::timestamp::ExchTime;
+use serde::{Deserialize, Serialize};
+
+pub const F12_HEADER_LEN: usize = 11;
+pub const F12_ENTRY_LEN: usize = 36;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct F12Entry {
+    pub symbol: Symbol,
+    open: Price,
+    high: Price,
+    low: Price,
+    last: Price,
+    volume: u64,
+    time: ExchTime,
+}
+
+/// Format 12 packs a group of symbols, each with open/high/low/last price, volume and
+/// last trade time, into one record.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct F12 {
+    pub mlen: u16,
+    cate: u8,
+    fcode: u8,
+    fver: u8,
+    pub no: u64,
+    pub entries: Vec<F12Entry>,
+}
+
+fn entry_at(raw: &[u8], base: usize) -> Result<F12Entry, ParseError> {
+    let symbol = Symbol::from_bytes(raw[base..base + 6].try_into().unwrap())?;
+    let price = |start: usize| -> Result<Price, ParseError> {
+        Ok(Price::from_scaled(bcd_at(raw, base + start, base + start + 5)? as i64))
+    };
+    bcd_at(raw, base + 30, base + 36)?;
+    Ok(F12Entry {
+        symbol,
+        open: price(6)?,
+        high: price(11)?,
+        low: price(16)?,
+        last: price(21)?,
+        volume: bcd_at(raw, base + 26, base + 30)?,
+        time: ExchTime::from_bcd(raw[base + 30..base + 36].try_into().unwrap()),
+    })
+}
+
+pub fn bytes2f12(raw: &[u8]) -> F12 {
+    try_bytes2f12(raw).unwrap()
+}
+
+pub fn try_bytes2f12(raw: &[u8]) -> Result<F12, ParseError> {
+    check_len(raw, F12_HEADER_LEN)?;
+    if raw[0] != ESC {
+        return Err(ParseError::MissingEsc(raw[0]));
+    }
+    let mlen = bcd_at(raw, 1, 3)? as usize;
+    let n_entry = bcd_at(raw, 10, 11)? as usize;
+    let body_len = F12_HEADER_LEN + F12_ENTRY_LEN * n_entry;
+    if mlen != body_len + TRAILER_LEN {
+        return Err(ParseError::LengthMismatch {
+            mlen,
+            expected: body_len + TRAILER_LEN,
+        });
+    }
+    check_len(raw, body_len)?;
+    let entries = (0..n_entry)
+        .map(|i| entry_at(raw, F12_HEADER_LEN + i * F12_ENTRY_LEN))
+        .collect::<Result<Vec<F12Entry>, ParseError>>()?;
+    Ok(F12 {
+        mlen: mlen as u16,
+        cate: bcd_at(raw, 3, 4)? as u8,
+        fcode: bcd_at(raw, 4, 5)? as u8,
+        fver: bcd_at(raw, 5, 6)? as u8,
+        no: bcd_at(raw, 6, 10)?,
+        entries,
+    })
+}
+
+impl F12 {
+    pub fn market(&self) -> Option<Market> {
+        Market::from_cate(self.cate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    const F12_RAW: &[u8] = &[
+        27, 3, 116, 1, 18, 3, 0, 0, 0, 1, 16, 48, 48, 53, 48, 32, 32, 0, 1, 66, 32, 0, 0, 1, 66, 69,
+        0, 0, 1, 65, 112, 0, 0, 1, 65, 128, 0, 0, 0, 67, 145, 19, 36, 84, 146, 67, 7, 48, 48, 53, 49,
+        32, 32, 0, 0, 96, 0, 0, 0, 0, 96, 21, 0, 0, 0, 89, 149, 0, 0, 0, 89, 149, 0, 0, 0, 0, 64, 19,
+        16, 23, 120, 151, 18, 48, 48, 53, 50, 32, 32, 0, 1, 48, 117, 0, 0, 1, 49, 0, 0, 0, 1, 48, 80,
+        0, 0, 1, 48, 96, 0, 0, 0, 3, 9, 19, 24, 53, 8, 149, 152, 48, 48, 53, 51, 32, 32, 0, 0, 104, 16,
+        0, 0, 0, 104, 32, 0, 0, 0, 104, 0, 0, 0, 0, 104, 16, 0, 0, 0, 0, 8, 19, 6, 38, 55, 49, 36, 48,
+        48, 53, 52, 32, 32, 0, 0, 48, 153, 0, 0, 0, 49, 4, 0, 0, 0, 48, 134, 0, 0, 0, 49, 4, 0, 0, 0, 0,
+        36, 18, 48, 0, 101, 100, 149, 48, 48, 53, 53, 32, 32, 0, 0, 36, 64, 0, 0, 0, 36, 72, 0, 0, 0, 36,
+        48, 0, 0, 0, 36, 48, 0, 0, 0, 7, 52, 19, 36, 69, 24, 117, 129, 48, 48, 53, 54, 32, 32, 0, 0, 51,
+        50, 0, 0, 0, 51, 69, 0, 0, 0, 51, 50, 0, 0, 0, 51, 51, 0, 0, 1, 3, 151, 19, 36, 86, 150, 89, 40,
+        48, 48, 53, 55, 32, 32, 0, 0, 152, 37, 0, 0, 0, 152, 37, 0, 0, 0, 151, 112, 0, 0, 0, 151, 149, 0,
+        0, 0, 0, 37, 18, 48, 0, 148, 6, 68, 48, 48, 54, 49, 32, 32, 0, 0, 35, 87, 0, 0, 0, 35, 130, 0, 0,
+        0, 35, 80, 0, 0, 0, 35, 114, 0, 0, 0, 3, 21, 19, 36, 49, 3, 148, 8, 48, 48, 54, 50, 48, 51, 0, 0,
+        104, 0, 0, 0, 0, 104, 64, 0, 0, 0, 103, 144, 0, 0, 0, 104, 64, 0, 0, 0, 0, 3, 18, 48, 2, 50, 148,
+        6, 105, 13, 10,
+    ];
+
+    #[test]
+    fn bytes2f12_test() {
+        let f12 = bytes2f12(F12_RAW);
+        assert_eq!(
+            (374, 1, 12, 3, 1, 10),
+            (f12.mlen, f12.cate, f12.fcode, f12.fver, f12.no, f12.entries.len())
+        );
+        let symbols: Vec<&str> = f12.entries.iter().map(|e| e.symbol.as_str()).collect();
+        assert_eq!(
+            vec!["0050", "0051", "0052", "0053", "0054", "0055", "0056", "0057", "0061", "006203"],
+            symbols
+        );
+    }
+
+    #[test_case(0, F12Entry {
+        symbol: "0050".parse().unwrap(),
+        open: Price::from_f64(142.2),
+        high: Price::from_f64(142.45),
+        low: Price::from_f64(141.7),
+        last: Price::from_f64(141.8),
+        volume: 4391,
+        time: "13:24:54.924307".parse().unwrap(),
+    }; "case 0050")]
+    #[test_case(9, F12Entry {
+        symbol: "006203".parse().unwrap(),
+        open: Price::from_f64(68.0),
+        high: Price::from_f64(68.4),
+        low: Price::from_f64(67.9),
+        last: Price::from_f64(68.4),
+        volume: 3,
+        time: "12:30:02.329406".parse().unwrap(),
+    }; "case 006203")]
+    fn bytes2f12_entry_testcase(idx: usize, expected: F12Entry) {
+        assert_eq!(expected, bytes2f12(F12_RAW).entries[idx])
+    }
+
+    #[test_case(F12_RAW[..200].to_vec(), ParseError::Truncated { need: 371, got: 200 }; "truncated")]
+    #[test_case([&F12_RAW[..10], &[0x9], &F12_RAW[11..]].concat(), ParseError::LengthMismatch { mlen: 374, expected: 338 }; "count mismatch")]
+    #[test_case([&F12_RAW[..17], &[0xa0], &F12_RAW[18..]].concat(), ParseError::InvalidBcd { offset: 17, byte: 0xa0 }; "invalid price")]
+    fn try_bytes2f12_error_testcase(input: Vec<u8>, expected: ParseError) {
+        assert_eq!(Err(expected), try_bytes2f12(&input))
+    }
+}
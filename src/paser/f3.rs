@@ -0,0 +1,127 @@
+use crate::paser::error::ParseError;
+use crate::paser::f6::{bcd_at, check_len, ESC, TRAILER_LEN};
+use crate::paser::market::Market;
+use crate::paser::price::Price;
+use crate::paser::timestamp::ExchTime;
+use serde::{Deserialize, Serialize};
+
+pub const F3_HEADER_LEN: usize = 11;
+// index code 9(4), time 9(12), value 9(6)V9(2). Not yet checked against the TWSE format 3
+// spec, no published layout was at hand when this was written.
+pub const F3_ENTRY_LEN: usize = 12;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct IndexUpdate {
+    pub code: u16,
+    pub time: ExchTime,
+    pub value: Price,
+}
+
+/// Format 3 market index record, carrying TAIEX and the sector indices as
+/// code/time/value groups.
+///
+/// The entry layout is unverified, see `F3_ENTRY_LEN`; do not rely on the decoded values
+/// until it has been checked against the spec and a captured record. Until then
+/// `Registry::default` does not decode it.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct F3 {
+    pub mlen: u16,
+    cate: u8,
+    fcode: u8,
+    fver: u8,
+    pub no: u64,
+    pub updates: Vec<IndexUpdate>,
+}
+
+fn update_at(raw: &[u8], base: usize) -> Result<IndexUpdate, ParseError> {
+    bcd_at(raw, base + 2, base + 8)?;
+    Ok(IndexUpdate {
+        code: bcd_at(raw, base, base + 2)? as u16,
+        time: ExchTime::from_bcd(raw[base + 2..base + 8].try_into().unwrap()),
+        value: Price::from_scaled(bcd_at(raw, base + 8, base + F3_ENTRY_LEN)? as i64 * 100),
+    })
+}
+
+pub fn bytes2f3(raw: &[u8]) -> F3 {
+    try_bytes2f3(raw).unwrap()
+}
+
+pub fn try_bytes2f3(raw: &[u8]) -> Result<F3, ParseError> {
+    check_len(raw, F3_HEADER_LEN)?;
+    if raw[0] != ESC {
+        return Err(ParseError::MissingEsc(raw[0]));
+    }
+    let mlen = bcd_at(raw, 1, 3)? as usize;
+    let n_entry = bcd_at(raw, 10, 11)? as usize;
+    let body_len = F3_HEADER_LEN + F3_ENTRY_LEN * n_entry;
+    if mlen != body_len + TRAILER_LEN {
+        return Err(ParseError::LengthMismatch {
+            mlen,
+            expected: body_len + TRAILER_LEN,
+        });
+    }
+    check_len(raw, body_len)?;
+    let updates = (0..n_entry)
+        .map(|i| update_at(raw, F3_HEADER_LEN + i * F3_ENTRY_LEN))
+        .collect::<Result<Vec<IndexUpdate>, ParseError>>()?;
+    Ok(F3 {
+        mlen: mlen as u16,
+        cate: bcd_at(raw, 3, 4)? as u8,
+        fcode: bcd_at(raw, 4, 5)? as u8,
+        fver: bcd_at(raw, 5, 6)? as u8,
+        no: bcd_at(raw, 6, 10)?,
+        updates,
+    })
+}
+
+impl F3 {
+    pub fn market(&self) -> Option<Market> {
+        Market::from_cate(self.cate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    // hand-built from the unverified layout, not a captured record
+    const F3_RAW: &[u8] = &[
+        0x1b, 0x0, 0x38, 0x1, 0x3, 0x4, 0x0, 0x0, 0x12, 0x34, 0x2, 0x0, 0x0, 0x9, 0x0, 0x5,
+        0x0, 0x0, 0x0, 0x1, 0x75, 0x12, 0x34, 0x0, 0x1, 0x9, 0x0, 0x5, 0x0, 0x0, 0x0, 0x0,
+        0x95, 0x43, 0x21, 0xbe, 0xd, 0xa,
+    ];
+
+    #[test]
+    fn bytes2f3_test() {
+        assert_eq!(
+            bytes2f3(F3_RAW),
+            F3 {
+                mlen: 38,
+                cate: 1,
+                fcode: 3,
+                fver: 4,
+                no: 1234,
+                updates: vec![
+                    IndexUpdate {
+                        code: 0,
+                        time: "09:00:05.000000".parse().unwrap(),
+                        value: Price::from_scaled(175123400),
+                    },
+                    IndexUpdate {
+                        code: 1,
+                        time: "09:00:05.000000".parse().unwrap(),
+                        value: Price::from_scaled(95432100),
+                    },
+                ],
+            }
+        )
+    }
+
+    #[test_case(F3_RAW[..30].to_vec(), ParseError::Truncated { need: 35, got: 30 }; "truncated")]
+    #[test_case([&F3_RAW[..10], &[0x3], &F3_RAW[11..]].concat(), ParseError::LengthMismatch { mlen: 38, expected: 50 }; "count mismatch")]
+    #[test_case([&F3_RAW[..20], &[0x0c], &F3_RAW[21..]].concat(), ParseError::InvalidBcd { offset: 20, byte: 0x0c }; "invalid value")]
+    fn try_bytes2f3_error_testcase(input: Vec<u8>, expected: ParseError) {
+        assert_eq!(Err(expected), try_bytes2f3(&input))
+    }
+}
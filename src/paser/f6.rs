@@ -1,26 +1,122 @@
 use crate::paser::bcd;
+use crate::paser::checksum;
+use crate::paser::depth::{Depth, Level, MAX_DEPTH};
+use crate::paser::error::ParseError;
+use crate::paser::fastbcd;
+use crate::paser::market::Market;
+use crate::paser::price::Price;
+use crate::paser::symbol::Symbol;
+use crate::paser::timestamp::{ExchTime, RecvTime};
 use serde::{Deserialize, Serialize};
-use std::str;
 // use chrono::prelude::{Local};
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
 pub struct BidAsk {
-    bid_price: [f64; 5],
-    bid_volume: [u64; 5],
-    ask_price: [f64; 5],
-    ask_volume: [u64; 5],
+    pub bid: Depth,
+    pub ask: Depth,
 }
+
+impl BidAsk {
+    pub fn best_bid(&self) -> Option<&Level> {
+        self.bid.best()
+    }
+
+    pub fn best_ask(&self) -> Option<&Level> {
+        self.ask.best()
+    }
+
+    pub fn spread(&self) -> Option<Price> {
+        Some(self.best_ask()?.price - self.best_bid()?.price)
+    }
+
+    pub fn mid(&self) -> Option<Price> {
+        Some((self.best_ask()?.price + self.best_bid()?.price) / 2)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Tick {
-    price: f64,
+    price: Price,
     volume: u64,
 }
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Quote {
-    bidask: BidAsk,
+    pub bidask: BidAsk,
     tick: Tick,
 }
 
+/// Two bits of the up/down byte: 01 at limit down, 10 at limit up. 11 has no documented
+/// meaning and is kept as is.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LimitState {
+    #[default]
+    None,
+    Up,
+    Down,
+    Other(u8),
+}
+
+impl LimitState {
+    pub fn from_bits(bits: u8) -> LimitState {
+        match bits & 0x03 {
+            0b10 => LimitState::Up,
+            0b01 => LimitState::Down,
+            0b00 => LimitState::None,
+            bits => LimitState::Other(bits),
+        }
+    }
+
+    pub fn to_bits(self) -> u8 {
+        match self {
+            LimitState::None => 0b00,
+            LimitState::Up => 0b10,
+            LimitState::Down => 0b01,
+            LimitState::Other(bits) => bits & 0x03,
+        }
+    }
+}
+
+/// Status byte: bit 7 simulated trade, 6 delayed open, 5 delayed close, 4 call auction,
+/// 3 opened, 2 closed. Bits 1-0 are reserved and kept as is.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Status {
+    pub simulation: bool,
+    pub delay_open: bool,
+    // wire name kept for existing consumers
+    #[serde(rename = "dalay_close")]
+    pub delay_close: bool,
+    pub auction: bool,
+    pub opened: bool,
+    pub closed: bool,
+    #[serde(default)]
+    pub reserved: u8,
+}
+
+impl Status {
+    pub fn from_byte(st: u8) -> Status {
+        Status {
+            simulation: (st & 0x80) != 0,
+            delay_open: (st & 0x40) != 0,
+            delay_close: (st & 0x20) != 0,
+            auction: (st & 0x10) != 0,
+            opened: (st & 0x08) != 0,
+            closed: (st & 0x04) != 0,
+            reserved: st & 0x03,
+        }
+    }
+
+    pub fn to_byte(&self) -> u8 {
+        (self.simulation as u8) << 7
+            | (self.delay_open as u8) << 6
+            | (self.delay_close as u8) << 5
+            | (self.auction as u8) << 4
+            | (self.opened as u8) << 3
+            | (self.closed as u8) << 2
+            | (self.reserved & 0x03)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct F6Header {
     pub mlen: u8,
@@ -28,18 +124,19 @@ pub struct F6Header {
     fcode: u8,
     fver: u8,
     pub no: u64,
-    symbol: String,
-    time: String,
+    symbol: Symbol,
+    time: ExchTime,
     n_match: u8,
     n_bid: u8,
     n_ask: u8,
+    // bmp bit 0, only the trade price and volume are disclosed
+    pub trade_only: bool,
+    pub trade_limit: LimitState,
+    pub bid_limit: LimitState,
+    pub ask_limit: LimitState,
     trice: u8,
-    simulation: bool,
-    delay_open: bool,
-    dalay_close: bool,
-    auction: bool,
-    opened: bool,
-    closed: bool,
+    #[serde(flatten)]
+    pub status: Status,
     volsum: u64,
 }
 
@@ -47,6 +144,22 @@ impl F6Header {
     pub fn n_info(&self) -> (usize, usize, usize) {
         (*&self.n_match as usize, *&self.n_bid as usize, *&self.n_ask as usize)
     }
+
+    pub fn symbol(&self) -> Symbol {
+        self.symbol
+    }
+
+    pub fn market(&self) -> Option<Market> {
+        Market::from_cate(self.cate)
+    }
+
+    pub fn time(&self) -> ExchTime {
+        self.time
+    }
+
+    pub fn volsum(&self) -> u64 {
+        self.volsum
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -56,10 +169,20 @@ pub struct F6 {
     // pub received: String,
 }
 
+impl F6 {
+    /// Last trade price and volume, if the record carries one.
+    pub fn trade(&self) -> Option<Level> {
+        match self.header.n_match {
+            0 => None,
+            _ => Some(Level::new(self.quote.tick.price, self.quote.tick.volume)),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct F6Received {
     pub f6: F6,
-    pub received: String,
+    pub received: RecvTime,
 }
 
 #[repr(C)]
@@ -79,15 +202,81 @@ pub struct Rawf6Fixed {
     volsum: [u8; 4],
 }
 
+pub const ESC: u8 = 0x1b;
+// fixed part of version 4
+pub const HEADER_LEN: usize = 29;
+pub const LEVEL_LEN: usize = 9;
+// checksum byte + CR/LF terminator
+pub const TRAILER_LEN: usize = 3;
+
+/// The only format 6 version whose layout is known. Records of any other version are refused
+/// rather than read with the offsets of this one.
+pub const F6_FVER: u8 = 4;
+
+fn check_fver(fver: u8) -> Result<(), ParseError> {
+    match fver {
+        F6_FVER => Ok(()),
+        _ => Err(ParseError::UnsupportedVersion { fcode: 6, fver }),
+    }
+}
+
+pub fn body_len(n_match: usize, n_bid: usize, n_ask: usize) -> usize {
+    HEADER_LEN + LEVEL_LEN * (n_match + n_bid + n_ask)
+}
+
+// ESC, mlen, cate, fcode and fver come before anything version specific
+fn check_version(raw: &[u8]) -> Result<(), ParseError> {
+    check_len(raw, 6)?;
+    if raw[0] != ESC {
+        return Err(ParseError::MissingEsc(raw[0]));
+    }
+    check_fver(bcd_at(raw, 5, 6)? as u8)
+}
+
+pub(crate) fn bcd_at(raw: &[u8], start: usize, end: usize) -> Result<u64, ParseError> {
+    bcd::try_bcdarr2num(&raw[start..end]).map_err(|idx| ParseError::InvalidBcd {
+        offset: start + idx,
+        byte: raw[start + idx],
+    })
+}
+
+pub(crate) fn check_len(raw: &[u8], need: usize) -> Result<(), ParseError> {
+    if raw.len() < need {
+        return Err(ParseError::Truncated {
+            need,
+            got: raw.len(),
+        });
+    }
+    Ok(())
+}
+
 pub fn bytes2mlen(raw: &[u8]) -> usize {
     bcd::bcdarr2num(&raw[1..3]) as usize
 }
 
+pub fn try_bytes2mlen(raw: &[u8]) -> Result<usize, ParseError> {
+    check_len(raw, 3)?;
+    if raw[0] != ESC {
+        return Err(ParseError::MissingEsc(raw[0]));
+    }
+    Ok(bcd_at(raw, 1, 3)? as usize)
+}
+
 pub fn bytes2fcode(raw: &[u8]) -> &'static u64 {
     bcd::bcd2num(raw[4])
 }
 
 pub fn bytes2header(raw: &[u8]) -> F6Header {
+    try_bytes2header(raw).unwrap()
+}
+
+pub fn try_bytes2header(raw: &[u8]) -> Result<F6Header, ParseError> {
+    check_version(raw)?;
+    check_len(raw, HEADER_LEN)?;
+    // every packed BCD field of the fixed part; symbol, bmp, ud and st are not BCD
+    bcd_at(raw, 1, 10)?;
+    bcd_at(raw, 16, 22)?;
+    bcd_at(raw, 25, 29)?;
     let fixed = Rawf6Fixed {
         esc_code: raw[0],
         mlen: raw[1..3].try_into().unwrap(),
@@ -103,81 +292,305 @@ pub fn bytes2header(raw: &[u8]) -> F6Header {
         volsum: raw[25..29].try_into().unwrap(),
     };
     // println!("{:?}", fixed);
+    let symbol = Symbol::from_bytes(fixed.symbol)?;
     let header = F6Header {
         mlen: bcd::bcdarr2num(&fixed.mlen) as u8,
         cate: *bcd::bcd2num(fixed.cate) as u8,
         fcode: *bcd::bcd2num(fixed.fcode) as u8,
         fver: *bcd::bcd2num(fixed.fver) as u8,
         no: bcd::bcdarr2num(&fixed.no),
-        symbol: String::from(str::from_utf8(&fixed.symbol).unwrap()),
-        time: bcd::bcd2time(fixed.time),
+        symbol,
+        time: ExchTime::from_bcd(fixed.time),
         n_match: (fixed.bmp & 0x80) >> 7,
         n_bid: (fixed.bmp & 0x70) >> 4,
         n_ask: (fixed.bmp & 0x0E) >> 1,
+        trade_only: (fixed.bmp & 0x01) != 0,
+        trade_limit: LimitState::from_bits(fixed.ud >> 6),
+        bid_limit: LimitState::from_bits(fixed.ud >> 4),
+        ask_limit: LimitState::from_bits(fixed.ud >> 2),
         trice: (fixed.ud & 0x03),
-        simulation: (fixed.st & 0x80) != 0,
-        delay_open: (fixed.st & 0x40) != 0,
-        dalay_close: (fixed.st & 0x20) != 0,
-        auction: (fixed.st & 0x10) != 0,
-        opened: (fixed.st & 0x08) != 0,
-        closed: (fixed.st & 0x04) != 0,
+        status: Status::from_byte(fixed.st),
         volsum: bcd::bcdarr2num(&fixed.volsum),
     };
+    if header.n_bid > 5 || header.n_ask > 5 {
+        return Err(ParseError::LevelCount {
+            n_match: header.n_match,
+            n_bid: header.n_bid,
+            n_ask: header.n_ask,
+        });
+    }
     // println!("{:?}", header);
-    header
+    Ok(header)
 }
 
 pub fn bytes2quote(packbcd_arr: &[u8], n_match: usize, n_bid: usize, n_ask: usize) -> Quote {
-    let mut tick_price = 0.0;
-    let mut tick_volume = 0;
-    if n_match > 0 {
-        let tick_raw: &[u8; 9] = &packbcd_arr[..9 * n_match].try_into().unwrap();
-        tick_price = bcd::bcd2price(tick_raw[..5].try_into().unwrap());
-        tick_volume = bcd::bcd2volume(tick_raw[5..9].try_into().unwrap());
-    }
-    let ba_raw: &[u8] = &packbcd_arr[9 * n_match..];
-    let mut bid_price: [f64; 5] = [0.; 5];
-    let mut bid_volume: [u64; 5] = [0; 5];
-    let mut ask_price: [f64; 5] = [0.; 5];
-    let mut ask_volume: [u64; 5] = [0; 5];
-    for i in 0..5 {
-        if n_bid > i {
-            let r_raw = &ba_raw[i * 9..(i + 1) * 9];
-            bid_price[i] = bcd::bcd2price(r_raw[..5].try_into().unwrap());
-            bid_volume[i] = bcd::bcd2volume(r_raw[5..9].try_into().unwrap());
-        }
-        if n_ask > i {
-            let r_raw = &ba_raw[(i + n_bid) * 9..(i + n_bid + 1) * 9];
-            ask_price[i] = bcd::bcd2price(r_raw[..5].try_into().unwrap());
-            ask_volume[i] = bcd::bcd2volume(r_raw[5..9].try_into().unwrap());
+    try_bytes2quote(packbcd_arr, n_match, n_bid, n_ask).unwrap()
+}
+
+pub fn try_bytes2quote(
+    packbcd_arr: &[u8],
+    n_match: usize,
+    n_bid: usize,
+    n_ask: usize,
+) -> Result<Quote, ParseError> {
+    quote_at(packbcd_arr, 0, n_match, n_bid, n_ask)
+}
+
+fn quote_at(
+    raw: &[u8],
+    base: usize,
+    n_match: usize,
+    n_bid: usize,
+    n_ask: usize,
+) -> Result<Quote, ParseError> {
+    let level_count = || ParseError::LevelCount {
+        n_match: n_match as u8,
+        n_bid: n_bid as u8,
+        n_ask: n_ask as u8,
+    };
+    if n_match > 1 || n_bid > 5 || n_ask > 5 {
+        return Err(level_count());
+    }
+    let n_level = n_match + n_bid + n_ask;
+    check_len(raw, base + LEVEL_LEN * n_level)?;
+    let mut nums = [(0, 0); 1 + 2 * MAX_DEPTH];
+    fastbcd::try_levels2num(&raw[base..], &mut nums[..n_level]).map_err(|idx| {
+        ParseError::InvalidBcd {
+            offset: base + idx,
+            byte: raw[base + idx],
         }
+    })?;
+    let level = |i: usize| {
+        let (price, volume) = nums[i];
+        Level::new(Price::from_scaled(price as i64), volume)
+    };
+    let mut tick = Tick {
+        price: Price::ZERO,
+        volume: 0,
+    };
+    if n_match > 0 {
+        let trade = level(0);
+        (tick.price, tick.volume) = (trade.price, trade.volume);
     }
-    Quote {
-        bidask: BidAsk {
-            bid_price,
-            bid_volume,
-            ask_price,
-            ask_volume,
-        },
-        tick: Tick {
-            price: tick_price,
-            volume: tick_volume,
-        },
+    let mut bidask = BidAsk::default();
+    for i in 0..n_bid {
+        bidask.bid.push(level(n_match + i)).map_err(|_| level_count())?;
+    }
+    for i in 0..n_ask {
+        bidask.ask.push(level(n_match + n_bid + i)).map_err(|_| level_count())?;
     }
+    Ok(Quote { bidask, tick })
+}
+
+/// Encodes `f6` into a full wire record, checksum and CR/LF included. `mlen` is computed and
+/// the bid and ask counts are those of the depth, so only the levels it holds are written.
+pub fn f62bytes(f6: &F6) -> Result<Vec<u8>, ParseError> {
+    let header = &f6.header;
+    check_fver(header.fver)?;
+    let (tick, bidask) = (&f6.quote.tick, &f6.quote.bidask);
+    let (n_match, n_bid, n_ask) = (header.n_info().0, bidask.bid.len(), bidask.ask.len());
+    let body_len = body_len(n_match, n_bid, n_ask);
+    let mut raw = vec![0u8; body_len + TRAILER_LEN];
+    raw[0] = ESC;
+    bcd::num2bcdarr(raw.len() as u64, &mut raw[1..3]);
+    raw[3] = bcd::num2bcd(header.cate);
+    raw[4] = bcd::num2bcd(header.fcode);
+    raw[5] = bcd::num2bcd(header.fver);
+    bcd::num2bcdarr(header.no, &mut raw[6..10]);
+    raw[10..16].copy_from_slice(header.symbol.as_bytes());
+    raw[16..22].copy_from_slice(&header.time.to_bcd());
+    raw[22] = (header.n_match << 7)
+        | (n_bid as u8) << 4
+        | (n_ask as u8) << 1
+        | header.trade_only as u8;
+    raw[23] = header.trade_limit.to_bits() << 6
+        | header.bid_limit.to_bits() << 4
+        | header.ask_limit.to_bits() << 2
+        | header.trice;
+    raw[24] = header.status.to_byte();
+    bcd::num2bcdarr(header.volsum, &mut raw[25..29]);
+    let mut put_level = |i: usize, price: Price, volume: u64| {
+        let start = HEADER_LEN + i * LEVEL_LEN;
+        bcd::num2bcdarr(price.scaled() as u64, &mut raw[start..start + 5]);
+        bcd::num2bcdarr(volume, &mut raw[start + 5..start + LEVEL_LEN]);
+    };
+    if n_match > 0 {
+        put_level(0, tick.price, tick.volume);
+    }
+    for (i, level) in bidask.bid.iter().enumerate() {
+        put_level(n_match + i, level.price, level.volume);
+    }
+    for (i, level) in bidask.ask.iter().enumerate() {
+        put_level(n_match + n_bid + i, level.price, level.volume);
+    }
+    raw[body_len] = checksum::xor_checksum(&raw[1..body_len]);
+    raw[body_len + 1..].copy_from_slice(&checksum::TERMINATOR);
+    Ok(raw)
 }
 
 pub fn bytes2f6(raw: &[u8]) -> F6 {
-    let header = bytes2header(raw);
-    let (n_match, n_bid, n_ask) = (
-        header.n_match as usize,
-        header.n_bid as usize,
-        header.n_ask as usize,
-    );
-    let tn = n_match + n_bid + n_ask;
-    F6 {
-        header: header,
-        quote: bytes2quote(&raw[29..29 + 9 * (tn)], n_match, n_bid, n_ask),
+    try_bytes2f6(raw).unwrap()
+}
+
+pub fn try_bytes2f6(raw: &[u8]) -> Result<F6, ParseError> {
+    let header = try_bytes2header(raw)?;
+    let (n_match, n_bid, n_ask) = header.n_info();
+    let body_len = body_len(n_match, n_bid, n_ask);
+    let mlen = bcd::bcdarr2num(&raw[1..3]) as usize;
+    if mlen != body_len + TRAILER_LEN {
+        return Err(ParseError::LengthMismatch {
+            mlen,
+            expected: body_len + TRAILER_LEN,
+        });
+    }
+    let quote = quote_at(raw, HEADER_LEN, n_match, n_bid, n_ask)?;
+    Ok(F6 {
+        header,
+        quote,
         // received: Local::now().to_rfc3339(),
+    })
+}
+
+/// Borrowed view over a raw format 6 record. `new` checks the framing and that every BCD
+/// field is valid, so it accepts what `try_bytes2f6` accepts; fields are decoded when
+/// accessed, so filtering by symbol or reading the last trade skips the rest.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct F6Ref<'a> {
+    raw: &'a [u8],
+}
+
+impl<'a> F6Ref<'a> {
+    pub fn new(raw: &'a [u8]) -> Result<F6Ref<'a>, ParseError> {
+        check_version(raw)?;
+        check_len(raw, HEADER_LEN)?;
+        bcd_at(raw, 1, 10)?;
+        bcd_at(raw, 16, 22)?;
+        bcd_at(raw, 25, 29)?;
+        let f6ref = F6Ref { raw };
+        let (n_match, n_bid, n_ask) = f6ref.n_info();
+        if n_bid > 5 || n_ask > 5 {
+            return Err(ParseError::LevelCount {
+                n_match: n_match as u8,
+                n_bid: n_bid as u8,
+                n_ask: n_ask as u8,
+            });
+        }
+        let body_len = body_len(n_match, n_bid, n_ask);
+        let mlen = bcd_at(raw, 1, 3)? as usize;
+        if mlen != body_len + TRAILER_LEN {
+            return Err(ParseError::LengthMismatch {
+                mlen,
+                expected: body_len + TRAILER_LEN,
+            });
+        }
+        check_len(raw, body_len)?;
+        Symbol::from_bytes(raw[10..16].try_into().unwrap())?;
+        let base = HEADER_LEN;
+        let mut levels = [(0, 0); 1 + 2 * MAX_DEPTH];
+        fastbcd::try_levels2num(&raw[base..], &mut levels[..n_match + n_bid + n_ask]).map_err(
+            |idx| ParseError::InvalidBcd {
+                offset: base + idx,
+                byte: raw[base + idx],
+            },
+        )?;
+        Ok(f6ref)
+    }
+
+    pub fn raw(&self) -> &'a [u8] {
+        self.raw
+    }
+
+    pub fn no(&self) -> u64 {
+        bcd::bcdarr2num(&self.raw[6..10])
+    }
+
+    pub fn symbol(&self) -> Symbol {
+        // checked in new
+        Symbol::from_bytes(self.raw[10..16].try_into().unwrap()).unwrap()
+    }
+
+    pub fn time(&self) -> ExchTime {
+        ExchTime::from_bcd(self.raw[16..22].try_into().unwrap())
+    }
+
+    pub fn n_info(&self) -> (usize, usize, usize) {
+        let bmp = self.raw[22];
+        (
+            ((bmp & 0x80) >> 7) as usize,
+            ((bmp & 0x70) >> 4) as usize,
+            ((bmp & 0x0E) >> 1) as usize,
+        )
+    }
+
+    pub fn fver(&self) -> u8 {
+        *bcd::bcd2num(self.raw[5]) as u8
+    }
+
+    pub fn market(&self) -> Option<Market> {
+        Market::from_cate(*bcd::bcd2num(self.raw[3]) as u8)
+    }
+
+    pub fn volsum(&self) -> u64 {
+        bcd::bcdarr2num(&self.raw[25..29])
+    }
+
+    /// Limit state of the trade, best bid and best ask.
+    pub fn limits(&self) -> (LimitState, LimitState, LimitState) {
+        let ud = self.raw[23];
+        (
+            LimitState::from_bits(ud >> 6),
+            LimitState::from_bits(ud >> 4),
+            LimitState::from_bits(ud >> 2),
+        )
+    }
+
+    pub fn status(&self) -> Status {
+        Status::from_byte(self.raw[24])
+    }
+
+    fn level(&self, i: usize) -> Level {
+        let start = HEADER_LEN + i * LEVEL_LEN;
+        Level::new(
+            bcd::bcd2price(self.raw[start..start + 5].try_into().unwrap()),
+            bcd::bcd2volume(self.raw[start + 5..start + LEVEL_LEN].try_into().unwrap()),
+        )
+    }
+
+    /// `F6::trade` without decoding the quote.
+    pub fn trade(&self) -> Option<Level> {
+        let (n_match, _, _) = self.n_info();
+        if n_match == 0 {
+            return None;
+        }
+        Some(self.level(0))
+    }
+
+    pub fn bid(&self, i: usize) -> Option<Level> {
+        let (n_match, n_bid, _) = self.n_info();
+        if i >= n_bid {
+            return None;
+        }
+        Some(self.level(n_match + i))
+    }
+
+    pub fn ask(&self, i: usize) -> Option<Level> {
+        let (n_match, n_bid, n_ask) = self.n_info();
+        if i >= n_ask {
+            return None;
+        }
+        Some(self.level(n_match + n_bid + i))
+    }
+
+    pub fn to_f6(&self) -> Result<F6, ParseError> {
+        try_bytes2f6(self.raw)
+    }
+}
+
+impl<'a> TryFrom<F6Ref<'a>> for F6 {
+    type Error = ParseError;
+
+    fn try_from(f6ref: F6Ref<'a>) -> Result<F6, ParseError> {
+        f6ref.to_f6()
     }
 }
 
@@ -187,8 +600,18 @@ extern crate test_case;
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::paser::fixtures::F6_RAW;
+    use proptest::prelude::*;
     use test_case::test_case;
 
+    fn level(price: f64, volume: u64) -> Level {
+        Level::new(Price::from_f64(price), volume)
+    }
+
+    fn depth(levels: &[(f64, u64)]) -> Depth {
+        levels.iter().map(|(price, volume)| level(*price, *volume)).collect()
+    }
+
     #[test]
     fn bytes2f6_test() {
         assert_eq!(
@@ -210,29 +633,34 @@ mod tests {
                     fcode: 6,
                     fver: 4,
                     no: 109359,
-                    symbol: String::from("911616"),
-                    time: String::from("09:00:00.140866"),
+                    symbol: "911616".parse().unwrap(),
+                    time: "09:00:00.140866".parse().unwrap(),
                     n_match: 1,
                     n_bid: 5,
                     n_ask: 5,
+                    trade_only: false,
+                    trade_limit: LimitState::None,
+                    bid_limit: LimitState::None,
+                    ask_limit: LimitState::None,
                     trice: 0,
-                    simulation: false,
-                    delay_open: false,
-                    dalay_close: false,
-                    auction: false,
-                    opened: true,
-                    closed: false,
+                    status: Status {
+                        simulation: false,
+                        delay_open: false,
+                        delay_close: false,
+                        auction: false,
+                        opened: true,
+                        closed: false,
+                        reserved: 0,
+                    },
                     volsum: 6
                 },
                 quote: Quote {
                     bidask: BidAsk {
-                        bid_price: [1.82, 1.81, 1.8, 1.76, 1.75],
-                        bid_volume: [6, 5, 16, 28, 20],
-                        ask_price: [1.93, 1.94, 1.95, 1.96, 1.97],
-                        ask_volume: [8, 1, 1, 25, 26],
+                        bid: depth(&[(1.82, 6), (1.81, 5), (1.8, 16), (1.76, 28), (1.75, 20)]),
+                        ask: depth(&[(1.93, 8), (1.94, 1), (1.95, 1), (1.96, 25), (1.97, 26)]),
                     },
                     tick: Tick {
-                        price: 1.82,
+                        price: Price::from_f64(1.82),
                         volume: 6,
                     },
                 }
@@ -240,39 +668,40 @@ mod tests {
         )
     }
 
-    #[test_case(&[
-        0x1b, 0x0, 0x41, 0x1, 0x6, 0x4, 0x0, 0x0, 0x0, 0x11, 0x30, 0x30, 0x36, 0x33, 0x32,
-        0x52, 0x8, 0x30, 0x0, 0x92, 0x9, 0x15, 0x10, 0x0, 0x80, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
-        0x6, 0x32, 0x0, 0x0, 0x0, 0x0, 0x1, 0x25,
-    ], F6{header: F6Header {
+    #[test_case(F6_RAW, F6{header: F6Header {
         mlen: 41,
         cate: 1,
         fcode: 6,
         fver: 4,
         no: 11,
-        symbol: String::from("00632R"),
-        time: String::from("08:30:00.920915"),
+        symbol: "00632R".parse().unwrap(),
+        time: "08:30:00.920915".parse().unwrap(),
         n_match: 0,
         n_bid: 1,
         n_ask: 0,
+        trade_only: false,
+        trade_limit: LimitState::None,
+        bid_limit: LimitState::None,
+        ask_limit: LimitState::None,
         trice: 0,
-        simulation: true,
-        delay_open: false,
-        dalay_close: false,
-        auction: false,
-        opened: false,
-        closed: false,
+        status: Status {
+            simulation: true,
+            delay_open: false,
+            delay_close: false,
+            auction: false,
+            opened: false,
+            closed: false,
+            reserved: 0,
+        },
         volsum: 0
         },
         quote: Quote {
             bidask: BidAsk {
-                bid_price: [6.32, 0.0, 0.0, 0.0, 0.0],
-                bid_volume: [1, 0, 0, 0, 0],
-                ask_price: [0.0, 0.0, 0.0, 0.0, 0.0],
-                ask_volume: [0, 0, 0, 0, 0],
+                bid: depth(&[(6.32, 1)]),
+                ask: depth(&[]),
             },
             tick: Tick {
-                price: 0.0,
+                price: Price::ZERO,
                 volume: 0,
             },
         }
@@ -369,18 +798,25 @@ mod tests {
                 fcode: 6,
                 fver: 4,
                 no: 109359,
-                symbol: String::from("911616"),
-                time: String::from("09:00:00.140866"),
+                symbol: "911616".parse().unwrap(),
+                time: "09:00:00.140866".parse().unwrap(),
                 n_match: 1,
                 n_bid: 5,
                 n_ask: 5,
+                trade_only: false,
+                trade_limit: LimitState::None,
+                bid_limit: LimitState::None,
+                ask_limit: LimitState::None,
                 trice: 0,
-                simulation: false,
-                delay_open: false,
-                dalay_close: false,
-                auction: false,
-                opened: true,
-                closed: false,
+                status: Status {
+                    simulation: false,
+                    delay_open: false,
+                    delay_close: false,
+                    auction: false,
+                    opened: true,
+                    closed: false,
+                    reserved: 0,
+                },
                 volsum: 6
             }
         )
@@ -394,45 +830,55 @@ mod tests {
             fcode: 6,
             fver: 4,
             no: 109359,
-            symbol: String::from("911616"),
-            time: String::from("09:00:00.140866"),
+            symbol: "911616".parse().unwrap(),
+            time: "09:00:00.140866".parse().unwrap(),
             n_match: 1,
             n_bid: 5,
             n_ask: 5,
+            trade_only: false,
+            trade_limit: LimitState::None,
+            bid_limit: LimitState::None,
+            ask_limit: LimitState::None,
             trice: 0,
-            simulation: false,
-            delay_open: false,
-            dalay_close: false,
-            auction: false,
-            opened: true,
-            closed: false,
+            status: Status {
+                simulation: false,
+                delay_open: false,
+                delay_close: false,
+                auction: false,
+                opened: true,
+                closed: false,
+                reserved: 0,
+            },
             volsum: 6
         };
         assert_eq!((1, 5, 5), f6.n_info())
     }
 
-    #[test_case(&[
-        0x1b, 0x0, 0x41, 0x1, 0x6, 0x4, 0x0, 0x0, 0x0, 0x11, 0x30, 0x30, 0x36, 0x33, 0x32,
-        0x52, 0x8, 0x30, 0x0, 0x92, 0x9, 0x15, 0x10, 0x0, 0x80, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
-        0x6, 0x32, 0x0, 0x0, 0x0, 0x0, 0x1, 0x25,
-    ], F6Header {
+    #[test_case(F6_RAW, F6Header {
         mlen: 41,
         cate: 1,
         fcode: 6,
         fver: 4,
         no: 11,
-        symbol: String::from("00632R"),
-        time: String::from("08:30:00.920915"),
+        symbol: "00632R".parse().unwrap(),
+        time: "08:30:00.920915".parse().unwrap(),
         n_match: 0,
         n_bid: 1,
         n_ask: 0,
+        trade_only: false,
+        trade_limit: LimitState::None,
+        bid_limit: LimitState::None,
+        ask_limit: LimitState::None,
         trice: 0,
-        simulation: true,
-        delay_open: false,
-        dalay_close: false,
-        auction: false,
-        opened: false,
-        closed: false,
+        status: Status {
+            simulation: true,
+            delay_open: false,
+            delay_close: false,
+            auction: false,
+            opened: false,
+            closed: false,
+            reserved: 0,
+        },
         volsum: 0
     }; "case bid only1")]
     fn bytes2header_testcase(input: &[u8], expected: F6Header) {
@@ -444,13 +890,11 @@ mod tests {
         assert_eq!(
             Quote {
                 bidask: BidAsk {
-                    bid_price: [545.0, 541.0, 540.0, 530.0, 522.0],
-                    bid_volume: [1, 1, 1, 2, 24],
-                    ask_price: [555.0, 558.0, 560.0, 561.0, 562.0],
-                    ask_volume: [1, 1, 1, 2, 1],
+                    bid: depth(&[(545.0, 1), (541.0, 1), (540.0, 1), (530.0, 2), (522.0, 24)]),
+                    ask: depth(&[(555.0, 1), (558.0, 1), (560.0, 1), (561.0, 2), (562.0, 1)]),
                 },
                 tick: Tick {
-                    price: 552.0,
+                    price: Price::from_f64(552.0),
                     volume: 2,
                 },
             },
@@ -481,13 +925,11 @@ mod tests {
         0x0, 0x0, 0x1
     ], 1, 5, 5, Quote {
         bidask: BidAsk {
-            bid_price: [545.0, 541.0, 540.0, 530.0, 522.0],
-            bid_volume: [1, 1, 1, 2, 24],
-            ask_price: [555.0, 558.0, 560.0, 561.0, 562.0],
-            ask_volume: [1, 1, 1, 2, 1],
+            bid: depth(&[(545.0, 1), (541.0, 1), (540.0, 1), (530.0, 2), (522.0, 24)]),
+            ask: depth(&[(555.0, 1), (558.0, 1), (560.0, 1), (561.0, 2), (562.0, 1)]),
         },
         tick: Tick {
-            price: 552.0,
+            price: Price::from_f64(552.0),
             volume: 2,
         },
     }; "case1")]
@@ -500,13 +942,11 @@ mod tests {
         0x0, 0x0, 0x25, 0x0, 0x0, 0x1, 0x97, 0x0, 0x0, 0x0, 0x0, 0x26,
     ], 1, 5, 5, Quote {
         bidask: BidAsk {
-            bid_price: [1.82, 1.81, 1.8, 1.76, 1.75],
-            bid_volume: [6, 5, 16, 28, 20],
-            ask_price: [1.93, 1.94, 1.95, 1.96, 1.97],
-            ask_volume: [8, 1, 1, 25, 26],
+            bid: depth(&[(1.82, 6), (1.81, 5), (1.8, 16), (1.76, 28), (1.75, 20)]),
+            ask: depth(&[(1.93, 8), (1.94, 1), (1.95, 1), (1.96, 25), (1.97, 26)]),
         },
         tick: Tick {
-            price: 1.82,
+            price: Price::from_f64(1.82),
             volume: 6,
         },
     }; "case2")]
@@ -516,13 +956,11 @@ mod tests {
         0, 0, 64, 181, 13, 10,
     ], 0, 3, 4, Quote {
         bidask: BidAsk {
-            bid_price: [7.06, 7.05, 6.8, 0.0, 0.0], 
-            bid_volume: [10, 10, 2, 0, 0], 
-            ask_price: [6.35, 7.07, 7.08, 7.26, 0.0], 
-            ask_volume: [4, 10, 10, 40, 0],
+            bid: depth(&[(7.06, 10), (7.05, 10), (6.8, 2)]),
+            ask: depth(&[(6.35, 4), (7.07, 10), (7.08, 10), (7.26, 40)]),
         },
         tick: Tick {
-            price: 0.0, volume: 0
+            price: Price::ZERO, volume: 0
         },
     }; "case3")]
     fn bytes2quote_testcase(
@@ -534,4 +972,381 @@ mod tests {
     ) {
         assert_eq!(expected, bytes2quote(input, n_match, n_bid, n_ask))
     }
+
+    fn corrupt(at: usize, byte: u8) -> Vec<u8> {
+        let mut raw = F6_RAW.to_vec();
+        raw[at] = byte;
+        raw
+    }
+
+    #[test]
+    fn try_bytes2f6_test() {
+        assert_eq!(Ok(bytes2f6(F6_RAW)), try_bytes2f6(F6_RAW));
+    }
+
+    #[test_case(F6_RAW[..20].to_vec(), ParseError::Truncated { need: 29, got: 20 }; "truncated header")]
+    #[test_case(F6_RAW[..35].to_vec(), ParseError::Truncated { need: 38, got: 35 }; "truncated quote")]
+    #[test_case(corrupt(0, 0x1c), ParseError::MissingEsc(0x1c); "missing esc")]
+    #[test_case(corrupt(9, 0x1a), ParseError::InvalidBcd { offset: 9, byte: 0x1a }; "invalid bcd no")]
+    #[test_case(corrupt(33, 0xf0), ParseError::InvalidBcd { offset: 33, byte: 0xf0 }; "invalid bcd price")]
+    #[test_case(corrupt(22, 0x60), ParseError::LevelCount { n_match: 0, n_bid: 6, n_ask: 0 }; "too many bids")]
+    #[test_case(corrupt(12, 0xff), ParseError::InvalidSymbol([0x30, 0x30, 0xff, 0x33, 0x32, 0x52]); "invalid symbol")]
+    #[test_case(corrupt(2, 0x50), ParseError::LengthMismatch { mlen: 50, expected: 41 }; "length mismatch")]
+    fn try_bytes2f6_error_testcase(input: Vec<u8>, expected: ParseError) {
+        assert_eq!(Err(expected), try_bytes2f6(&input))
+    }
+
+    #[test_case(&[0x1b, 0x1, 0x31], Ok(131); "ok")]
+    #[test_case(&[0x1b, 0x1], Err(ParseError::Truncated { need: 3, got: 2 }); "truncated")]
+    #[test_case(&[0x0, 0x1, 0x31], Err(ParseError::MissingEsc(0x0)); "missing esc")]
+    #[test_case(&[0x1b, 0x1, 0x3a], Err(ParseError::InvalidBcd { offset: 2, byte: 0x3a }); "invalid bcd")]
+    fn try_bytes2mlen_testcase(input: &[u8], expected: Result<usize, ParseError>) {
+        assert_eq!(expected, try_bytes2mlen(input))
+    }
+
+    #[test_case(&[0x0, 0x0, 0x1], 0, 1, 0, ParseError::Truncated { need: 9, got: 3 }; "truncated")]
+    #[test_case(&[], 2, 0, 0, ParseError::LevelCount { n_match: 2, n_bid: 0, n_ask: 0 }; "match out of range")]
+    #[test_case(&[], 0, 0, 7, ParseError::LevelCount { n_match: 0, n_bid: 0, n_ask: 7 }; "ask out of range")]
+    fn try_bytes2quote_error_testcase(
+        input: &[u8],
+        n_match: usize,
+        n_bid: usize,
+        n_ask: usize,
+        expected: ParseError,
+    ) {
+        assert_eq!(Err(expected), try_bytes2quote(input, n_match, n_bid, n_ask))
+    }
+
+    #[test]
+    fn f6ref_test() {
+        let raw = &[
+            0x1b, 0x1, 0x31, 0x1, 0x6, 0x4, 0x0, 0x10, 0x93, 0x59, 0x39, 0x31, 0x31, 0x36,
+            0x31, 0x36, 0x9, 0x0, 0x0, 0x14, 0x8, 0x66, 0xda, 0x0, 0x8, 0x0, 0x0, 0x0, 0x6,
+            0x0, 0x0, 0x1, 0x82, 0x0, 0x0, 0x0, 0x0, 0x6, 0x0, 0x0, 0x1, 0x82, 0x0, 0x0, 0x0,
+            0x0, 0x6, 0x0, 0x0, 0x1, 0x81, 0x0, 0x0, 0x0, 0x0, 0x5, 0x0, 0x0, 0x1, 0x80, 0x0,
+            0x0, 0x0, 0x0, 0x16, 0x0, 0x0, 0x1, 0x76, 0x0, 0x0, 0x0, 0x0, 0x28, 0x0, 0x0, 0x1,
+            0x75, 0x0, 0x0, 0x0, 0x0, 0x20, 0x0, 0x0, 0x1, 0x93, 0x0, 0x0, 0x0, 0x0, 0x8, 0x0,
+            0x0, 0x1, 0x94, 0x0, 0x0, 0x0, 0x0, 0x1, 0x0, 0x0, 0x1, 0x95, 0x0, 0x0, 0x0, 0x0,
+            0x1, 0x0, 0x0, 0x1, 0x96, 0x0, 0x0, 0x0, 0x0, 0x25, 0x0, 0x0, 0x1, 0x97, 0x0, 0x0,
+            0x0, 0x0, 0x26, 0xc6,
+        ];
+        let f6ref = F6Ref::new(raw).unwrap();
+        let f6 = bytes2f6(raw);
+        assert_eq!(f6.header.no, f6ref.no());
+        assert_eq!(f6.header.symbol, f6ref.symbol());
+        assert_eq!(f6.header.time, f6ref.time());
+        assert_eq!(ExchTime::from_micros(32_400_140_866), f6ref.time());
+        assert_eq!(f6.header.n_info(), f6ref.n_info());
+        assert_eq!(f6.header.volsum, f6ref.volsum());
+        assert_eq!(Some(Market::Twse), f6ref.market());
+        assert_eq!(f6.header.market(), f6ref.market());
+        assert_eq!(Some(level(1.82, 6)), f6ref.trade());
+        assert_eq!(Some(level(1.75, 20)), f6ref.bid(4));
+        assert_eq!(Some(level(1.93, 8)), f6ref.ask(0));
+        assert_eq!(None, f6ref.ask(5));
+        assert_eq!(Ok(f6), F6::try_from(f6ref));
+    }
+
+    #[test]
+    fn f6ref_bid_only_test() {
+        let f6ref = F6Ref::new(F6_RAW).unwrap();
+        assert_eq!(f6ref.symbol(), "00632R");
+        assert_eq!(None, f6ref.trade());
+        assert_eq!(Some(level(6.32, 1)), f6ref.bid(0));
+        assert_eq!(None, f6ref.bid(1));
+        assert_eq!(None, f6ref.ask(0));
+    }
+
+    #[test_case(F6_RAW[..35].to_vec(), ParseError::Truncated { need: 38, got: 35 }; "truncated")]
+    #[test_case(corrupt(0, 0x1c), ParseError::MissingEsc(0x1c); "missing esc")]
+    #[test_case(corrupt(22, 0x60), ParseError::LevelCount { n_match: 0, n_bid: 6, n_ask: 0 }; "too many bids")]
+    #[test_case(corrupt(2, 0x50), ParseError::LengthMismatch { mlen: 50, expected: 41 }; "length mismatch")]
+    #[test_case(corrupt(12, 0xff), ParseError::InvalidSymbol([0x30, 0x30, 0xff, 0x33, 0x32, 0x52]); "invalid symbol")]
+    #[test_case(corrupt(8, 0x0b), ParseError::InvalidBcd { offset: 8, byte: 0x0b }; "invalid no")]
+    #[test_case(corrupt(17, 0xa0), ParseError::InvalidBcd { offset: 17, byte: 0xa0 }; "invalid time")]
+    #[test_case(corrupt(27, 0x0f), ParseError::InvalidBcd { offset: 27, byte: 0x0f }; "invalid volsum")]
+    #[test_case(corrupt(31, 0x6c), ParseError::InvalidBcd { offset: 31, byte: 0x6c }; "invalid price")]
+    fn f6ref_error_testcase(input: Vec<u8>, expected: ParseError) {
+        assert_eq!(Err(expected), F6Ref::new(&input))
+    }
+
+    #[test_case(0x00, LimitState::None; "none")]
+    #[test_case(0x02, LimitState::Up; "up")]
+    #[test_case(0x01, LimitState::Down; "down")]
+    #[test_case(0x03, LimitState::Other(0x03); "reserved")]
+    #[test_case(0x0a, LimitState::Up; "ignores higher bits")]
+    fn limit_state_from_bits_testcase(input: u8, expected: LimitState) {
+        assert_eq!(expected, LimitState::from_bits(input));
+        assert_eq!(input & 0x03, expected.to_bits());
+    }
+
+    #[test]
+    fn bytes2header_limits_test() {
+        // trade at limit up, bid at limit down, ask at limit up
+        let header = bytes2header(&corrupt(23, 0x98));
+        assert_eq!(
+            (LimitState::Up, LimitState::Down, LimitState::Up),
+            (header.trade_limit, header.bid_limit, header.ask_limit)
+        );
+        assert_eq!(
+            (LimitState::Up, LimitState::Down, LimitState::Up),
+            F6Ref::new(&corrupt(23, 0x98)).unwrap().limits()
+        );
+        assert!(!header.trade_only);
+        assert!(bytes2header(&corrupt(22, 0x11)).trade_only);
+    }
+
+    #[test]
+    fn status_test() {
+        let status = Status::from_byte(0x96);
+        assert_eq!(
+            Status {
+                simulation: true,
+                delay_open: false,
+                delay_close: false,
+                auction: true,
+                opened: false,
+                closed: true,
+                reserved: 0x02,
+            },
+            status
+        );
+        assert_eq!(0x96, status.to_byte());
+        assert_eq!(
+            r#"{"simulation":true,"delay_open":false,"dalay_close":false,"auction":true,"opened":false,"closed":true,"reserved":2}"#,
+            serde_json::to_string(&status).unwrap()
+        );
+    }
+
+    #[test]
+    fn bytes2f6_version_test() {
+        let f6 = bytes2f6(F6_RAW);
+        assert_eq!((F6_FVER, 41), (f6.header.fver, f6.header.mlen));
+        assert_eq!(f6.header.symbol, "00632R");
+        assert_eq!("08:30:00.920915", f6.header.time.to_string());
+        assert_eq!((0, 1, 0), f6.header.n_info());
+        assert_eq!(0, f6.header.volsum);
+        assert_eq!(Some(&level(6.32, 1)), f6.quote.bidask.best_bid());
+        let f6ref = F6Ref::new(F6_RAW).unwrap();
+        assert_eq!(F6_FVER, f6ref.fver());
+        assert_eq!(Some(level(6.32, 1)), f6ref.bid(0));
+        assert_eq!(Ok(f6), f6ref.to_f6());
+    }
+
+    #[test_case(corrupt(5, 0x5), ParseError::UnsupportedVersion { fcode: 6, fver: 5 }; "version 5")]
+    #[test_case(corrupt(5, 0x3), ParseError::UnsupportedVersion { fcode: 6, fver: 3 }; "version 3")]
+    #[test_case(corrupt(5, 0xa), ParseError::InvalidBcd { offset: 5, byte: 0xa }; "invalid version")]
+    fn unsupported_version_testcase(input: Vec<u8>, expected: ParseError) {
+        assert_eq!(Err(expected.clone()), try_bytes2f6(&input));
+        assert_eq!(Err(expected), F6Ref::new(&input));
+    }
+
+    #[test]
+    fn bidask_best_test() {
+        let bidask = BidAsk {
+            bid: depth(&[(1.82, 6), (1.81, 5)]),
+            ask: depth(&[(1.93, 8)]),
+        };
+        assert_eq!(Some(&level(1.82, 6)), bidask.best_bid());
+        assert_eq!(Some(&level(1.93, 8)), bidask.best_ask());
+        assert_eq!(Some(Price::from_f64(0.11)), bidask.spread());
+        assert_eq!(Some(Price::from_f64(1.875)), bidask.mid());
+        let bid_only = bytes2f6(F6_RAW).quote.bidask;
+        assert_eq!((None, None), (bid_only.spread(), bid_only.mid()));
+        assert_eq!(
+            r#"{"bid":[{"price":6.32,"volume":1}],"ask":[]}"#,
+            serde_json::to_string(&bid_only).unwrap()
+        );
+    }
+
+    #[test]
+    fn f62bytes_test() {
+        assert_eq!(
+            Ok([F6_RAW, &checksum::TERMINATOR].concat()),
+            f62bytes(&bytes2f6(F6_RAW))
+        );
+        // a header claiming more levels than the depth holds encodes only those held
+        let mut f6 = bytes2f6(F6_RAW);
+        f6.header.n_bid = 3;
+        assert_eq!(
+            Ok([F6_RAW, &checksum::TERMINATOR].concat()),
+            f62bytes(&f6)
+        );
+        f6.header.fver = 3;
+        assert_eq!(
+            Err(ParseError::UnsupportedVersion { fcode: 6, fver: 3 }),
+            f62bytes(&f6)
+        );
+    }
+
+    #[test]
+    fn f62bytes_file_roundtrip_test() {
+        let data = std::fs::read("tests/data/f6_01000001_01001000_TP03.new").unwrap();
+        let mut pos = 0;
+        let mut count = 0;
+        while pos < data.len() {
+            let raw = &data[pos..pos + bytes2mlen(&data[pos..])];
+            assert_eq!(raw, &f62bytes(&bytes2f6(raw)).unwrap()[..], "record {}", count);
+            pos += raw.len();
+            count += 1;
+        }
+        assert_eq!(1000, count);
+    }
+
+    fn arb_f6() -> impl Strategy<Value = F6> {
+        let levels = (0..=1u8, 0..=5u8, 0..=5u8);
+        let limits = prop::array::uniform3((0..=3u8).prop_map(LimitState::from_bits));
+        let flags = (any::<bool>(), limits, 0..=3u8, any::<u8>());
+        let fields = (0..=99_999_999u64, "[0-9A-Z]{4,6}", 0..86_400_000_000u64, 0..=99_999_999u64);
+        let price = (0..=9_999_999_999i64).prop_map(Price::from_scaled);
+        let quote = (
+            prop::array::uniform11(price),
+            prop::array::uniform11(0..=99_999_999u64),
+        );
+        (levels, flags, fields, quote).prop_map(
+            |(
+                (n_match, n_bid, n_ask),
+                (trade_only, limits, trice, st),
+                (no, symbol, time, volsum),
+                (prices, volumes),
+            )| {
+                let level = |i: usize| Level::new(prices[i], volumes[i]);
+                let mut tick = Tick {
+                    price: Price::ZERO,
+                    volume: 0,
+                };
+                if n_match > 0 {
+                    (tick.price, tick.volume) = (prices[0], volumes[0]);
+                }
+                let bidask = BidAsk {
+                    bid: (0..n_bid as usize).map(|i| level(1 + i)).collect(),
+                    ask: (0..n_ask as usize).map(|i| level(6 + i)).collect(),
+                };
+                let quote = Quote { bidask, tick };
+                F6 {
+                    header: F6Header {
+                        mlen: (body_len(n_match as usize, n_bid as usize, n_ask as usize)
+                            + TRAILER_LEN) as u8,
+                        cate: 1,
+                        fcode: 6,
+                        fver: F6_FVER,
+                        no,
+                        symbol: symbol.parse().unwrap(),
+                        time: ExchTime::from_micros(time),
+                        n_match,
+                        n_bid,
+                        n_ask,
+                        trade_only,
+                        trade_limit: limits[0],
+                        bid_limit: limits[1],
+                        ask_limit: limits[2],
+                        trice,
+                        status: Status::from_byte(st),
+                        volsum,
+                    },
+                    quote,
+                }
+            },
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn f62bytes_roundtrip_proptest(f6 in arb_f6()) {
+            let raw = f62bytes(&f6).unwrap();
+            prop_assert_eq!(Ok(()), checksum::validate_record(&raw));
+            prop_assert_eq!(f6, bytes2f6(&raw));
+        }
+
+        #[test]
+        fn f6ref_matches_decoded_proptest(f6 in arb_f6()) {
+            let raw = f62bytes(&f6).unwrap();
+            let f6ref = F6Ref::new(&raw).unwrap();
+            let (tick, bidask) = (&f6.quote.tick, &f6.quote.bidask);
+            prop_assert_eq!(f6.header.no, f6ref.no());
+            prop_assert_eq!(f6.header.symbol, f6ref.symbol());
+            prop_assert_eq!(f6.header.time, f6ref.time());
+            prop_assert_eq!(f6.header.n_info(), f6ref.n_info());
+            prop_assert_eq!(f6.header.volsum, f6ref.volsum());
+            prop_assert_eq!(f6.header.status, f6ref.status());
+            let trade = (f6.header.n_match > 0).then(|| Level::new(tick.price, tick.volume));
+            prop_assert_eq!(trade, f6ref.trade());
+            for i in 0..=MAX_DEPTH {
+                prop_assert_eq!(bidask.bid.get(i).copied(), f6ref.bid(i));
+                prop_assert_eq!(bidask.ask.get(i).copied(), f6ref.ask(i));
+            }
+        }
+
+        #[test]
+        fn try_decoders_arbitrary_bytes_proptest(raw in prop::collection::vec(any::<u8>(), 0..160)) {
+            let _ = try_bytes2mlen(&raw);
+            let _ = try_bytes2header(&raw);
+            let decoded = try_bytes2f6(&raw);
+            check_f6ref(&raw, decoded)?;
+        }
+
+        #[test]
+        fn try_decoders_corrupted_proptest(
+            f6 in arb_f6(),
+            at in any::<prop::sample::Index>(),
+            byte in any::<u8>(),
+        ) {
+            let mut raw = f62bytes(&f6).unwrap();
+            let at = at.index(raw.len());
+            raw[at] = byte;
+            let _ = try_bytes2header(&raw);
+            let decoded = try_bytes2f6(&raw);
+            check_f6ref(&raw, decoded)?;
+        }
+
+        #[test]
+        fn try_bytes2f6_truncated_proptest(f6 in arb_f6(), at in any::<prop::sample::Index>()) {
+            let raw = f62bytes(&f6).unwrap();
+            let body_len = raw.len() - TRAILER_LEN;
+            let got = at.index(body_len);
+            let result = try_bytes2f6(&raw[..got]);
+            prop_assert!(matches!(result, Err(ParseError::Truncated { .. })), "{:?}", result);
+            prop_assert_eq!(result.err(), F6Ref::new(&raw[..got]).err());
+        }
+
+        #[test]
+        fn try_bytes2quote_proptest(
+            raw in prop::collection::vec(any::<u8>(), 0..120),
+            n_match in 0..3usize,
+            n_bid in 0..8usize,
+            n_ask in 0..8usize,
+        ) {
+            if let Ok(quote) = try_bytes2quote(&raw, n_match, n_bid, n_ask) {
+                prop_assert!(n_match <= 1);
+                prop_assert_eq!((n_bid, n_ask), (quote.bidask.bid.len(), quote.bidask.ask.len()));
+                prop_assert!(raw.len() >= LEVEL_LEN * (n_match + n_bid + n_ask));
+            }
+        }
+    }
+
+    // F6Ref::new must accept exactly the records try_bytes2f6 accepts and read back the same
+    // fields.
+    fn check_f6ref(raw: &[u8], decoded: Result<F6, ParseError>) -> Result<(), TestCaseError> {
+        let (f6, f6ref) = match (decoded, F6Ref::new(raw)) {
+            (Ok(f6), Ok(f6ref)) => (f6, f6ref),
+            (Ok(f6), Err(e)) => {
+                return Err(TestCaseError::fail(format!("{:?} rejected by F6Ref: {:?}", f6, e)))
+            }
+            (Err(e), Ok(f6ref)) => {
+                return Err(TestCaseError::fail(format!("{:?} accepted by F6Ref: {:?}", f6ref, e)))
+            }
+            (Err(_), Err(_)) => return Ok(()),
+        };
+        prop_assert_eq!(f6.header.no, f6ref.no());
+        prop_assert_eq!(f6.header.time, f6ref.time());
+        prop_assert_eq!(f6.header.volsum, f6ref.volsum());
+        prop_assert_eq!(f6.header.n_info(), f6ref.n_info());
+        prop_assert_eq!(f6.trade(), f6ref.trade());
+        for i in 0..=MAX_DEPTH {
+            prop_assert_eq!(f6.quote.bidask.bid.get(i).copied(), f6ref.bid(i));
+            prop_assert_eq!(f6.quote.bidask.ask.get(i).copied(), f6ref.ask(i));
+        }
+        prop_assert_eq!(Ok(f6), f6ref.to_f6());
+        Ok(())
+    }
 }
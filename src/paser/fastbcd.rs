@@ -0,0 +1,321 @@
+use crate::paser::bcd;
+
+// a quote level is a 9(5)V9(4) price followed by a 9(8) volume
+const PRICE_LEN: usize = 5;
+const VOLUME_LEN: usize = 4;
+const LEVEL_LEN: usize = PRICE_LEN + VOLUME_LEN;
+
+const NIBBLES: u64 = 0x0F0F_0F0F_0F0F_0F0F;
+const BYTES: u64 = 0x00FF_00FF_00FF_00FF;
+const WORDS: u64 = 0x0000_FFFF_0000_FFFF;
+
+/// Which level decoder `try_levels2num_with` runs.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Backend {
+    Scalar,
+    Sse2,
+    Avx2,
+}
+
+impl Backend {
+    /// Fastest backend the running CPU supports.
+    pub fn detect() -> Backend {
+        if Backend::Avx2.is_available() {
+            Backend::Avx2
+        } else if Backend::Sse2.is_available() {
+            Backend::Sse2
+        } else {
+            Backend::Scalar
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        match self {
+            Backend::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            Backend::Sse2 => is_x86_feature_detected!("sse2"),
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => false,
+        }
+    }
+}
+
+// true if any nibble is above 9, adding 6 carries into bit 4 of that byte
+#[inline]
+fn has_invalid(x: u64) -> bool {
+    let lo = x & NIBBLES;
+    let hi = (x >> 4) & NIBBLES;
+    ((lo + 0x0606_0606_0606_0606) | (hi + 0x0606_0606_0606_0606)) & 0x1010_1010_1010_1010 != 0
+}
+
+// up to 16 digits right-aligned in `x`, combined pairwise: digits, bytes, words, dwords
+#[inline]
+fn swar2num(x: u64) -> u64 {
+    let x = ((x >> 4) & NIBBLES) * 10 + (x & NIBBLES);
+    let x = ((x >> 8) & BYTES) * 100 + (x & BYTES);
+    let x = ((x >> 16) & WORDS) * 10_000 + (x & WORDS);
+    (x >> 32) * 100_000_000 + (x & 0xFFFF_FFFF)
+}
+
+#[inline]
+fn load_be(packbcd_arr: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf[8 - packbcd_arr.len()..].copy_from_slice(packbcd_arr);
+    u64::from_be_bytes(buf)
+}
+
+// price and volume of one level, right-aligned
+#[inline]
+fn load_level(level: &[u8]) -> (u64, u64) {
+    let level: &[u8; LEVEL_LEN] = level.try_into().unwrap();
+    let head = u64::from_be_bytes(level[..8].try_into().unwrap());
+    let volume = u32::from_be_bytes(level[PRICE_LEN..].try_into().unwrap());
+    (head >> 24, volume as u64)
+}
+
+/// Branchless `try_bcdarr2num` for up to 8 bytes.
+#[inline]
+pub fn try_bcd2num(packbcd_arr: &[u8]) -> Result<u64, usize> {
+    assert!(packbcd_arr.len() <= 8, "at most 8 bcd bytes fit a u64");
+    let x = load_be(packbcd_arr);
+    if has_invalid(x) {
+        return Err(first_invalid(packbcd_arr));
+    }
+    Ok(swar2num(x))
+}
+
+fn first_invalid(raw: &[u8]) -> usize {
+    raw.iter().position(|b| !bcd::is_valid_bcd(*b)).unwrap()
+}
+
+/// Decodes `out.len()` consecutive levels of `raw` into (scaled price, volume) pairs with the
+/// fastest available backend. On error returns the index of the first invalid byte, like
+/// decoding each field with `try_bcdarr2num` in order would.
+pub fn try_levels2num(raw: &[u8], out: &mut [(u64, u64)]) -> Result<(), usize> {
+    try_levels2num_with(Backend::detect(), raw, out)
+}
+
+/// Panics if `backend` is not available on this CPU or `raw` holds fewer than `out.len()`
+/// levels.
+pub fn try_levels2num_with(
+    backend: Backend,
+    raw: &[u8],
+    out: &mut [(u64, u64)],
+) -> Result<(), usize> {
+    assert!(
+        backend.is_available(),
+        "{:?} is not supported here",
+        backend
+    );
+    let raw = &raw[..LEVEL_LEN * out.len()];
+    let valid = match backend {
+        Backend::Scalar => levels2num_scalar(raw, out),
+        // checked by is_available above
+        #[cfg(target_arch = "x86_64")]
+        Backend::Sse2 => unsafe { x86::levels2num_sse2(raw, out) },
+        #[cfg(target_arch = "x86_64")]
+        Backend::Avx2 => unsafe { x86::levels2num_avx2(raw, out) },
+        #[cfg(not(target_arch = "x86_64"))]
+        _ => unreachable!(),
+    };
+    match valid {
+        true => Ok(()),
+        false => Err(first_invalid(raw)),
+    }
+}
+
+fn levels2num_scalar(raw: &[u8], out: &mut [(u64, u64)]) -> bool {
+    let mut invalid = false;
+    for (level, out) in raw.chunks_exact(LEVEL_LEN).zip(out) {
+        let (price, volume) = load_level(level);
+        invalid |= has_invalid(price) | has_invalid(volume);
+        *out = (swar2num(price), swar2num(volume));
+    }
+    !invalid
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use super::{load_level, LEVEL_LEN};
+    use std::arch::x86_64::*;
+
+    // swar2num on both 64-bit lanes, `invalid` collects bytes with a nibble above 9
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    unsafe fn lanes2num_sse2(v: __m128i, invalid: &mut __m128i) -> __m128i {
+        let nibbles = _mm_set1_epi8(0x0F);
+        let nine = _mm_set1_epi8(9);
+        let lo = _mm_and_si128(v, nibbles);
+        let hi = _mm_and_si128(_mm_srli_epi64(v, 4), nibbles);
+        *invalid = _mm_or_si128(
+            *invalid,
+            _mm_or_si128(_mm_cmpgt_epi8(lo, nine), _mm_cmpgt_epi8(hi, nine)),
+        );
+        let bytes = _mm_add_epi16(_mm_mullo_epi16(hi, _mm_set1_epi16(10)), lo);
+        let words = _mm_add_epi16(
+            _mm_mullo_epi16(_mm_srli_epi16(bytes, 8), _mm_set1_epi16(100)),
+            _mm_and_si128(bytes, _mm_set1_epi16(0xFF)),
+        );
+        let dwords = _mm_madd_epi16(words, _mm_set1_epi32(10_000 << 16 | 1));
+        _mm_add_epi64(
+            _mm_mul_epu32(_mm_srli_epi64(dwords, 32), _mm_set1_epi64x(100_000_000)),
+            _mm_and_si128(dwords, _mm_set1_epi64x(0xFFFF_FFFF)),
+        )
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn levels2num_sse2(raw: &[u8], out: &mut [(u64, u64)]) -> bool {
+        let mut invalid = _mm_setzero_si128();
+        for (level, out) in raw.chunks_exact(LEVEL_LEN).zip(out) {
+            let (price, volume) = load_level(level);
+            let v = _mm_set_epi64x(volume as i64, price as i64);
+            let mut lanes = [0u64; 2];
+            _mm_storeu_si128(
+                lanes.as_mut_ptr() as *mut __m128i,
+                lanes2num_sse2(v, &mut invalid),
+            );
+            *out = (lanes[0], lanes[1]);
+        }
+        _mm_movemask_epi8(invalid) == 0
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn lanes2num_avx2(v: __m256i, invalid: &mut __m256i) -> __m256i {
+        let nibbles = _mm256_set1_epi8(0x0F);
+        let nine = _mm256_set1_epi8(9);
+        let lo = _mm256_and_si256(v, nibbles);
+        let hi = _mm256_and_si256(_mm256_srli_epi64(v, 4), nibbles);
+        *invalid = _mm256_or_si256(
+            *invalid,
+            _mm256_or_si256(_mm256_cmpgt_epi8(lo, nine), _mm256_cmpgt_epi8(hi, nine)),
+        );
+        let bytes = _mm256_add_epi16(_mm256_mullo_epi16(hi, _mm256_set1_epi16(10)), lo);
+        let words = _mm256_add_epi16(
+            _mm256_mullo_epi16(_mm256_srli_epi16(bytes, 8), _mm256_set1_epi16(100)),
+            _mm256_and_si256(bytes, _mm256_set1_epi16(0xFF)),
+        );
+        let dwords = _mm256_madd_epi16(words, _mm256_set1_epi32(10_000 << 16 | 1));
+        _mm256_add_epi64(
+            _mm256_mul_epu32(
+                _mm256_srli_epi64(dwords, 32),
+                _mm256_set1_epi64x(100_000_000),
+            ),
+            _mm256_and_si256(dwords, _mm256_set1_epi64x(0xFFFF_FFFF)),
+        )
+    }
+
+    // two levels per register, an odd last level goes through the sse2 lanes
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn levels2num_avx2(raw: &[u8], out: &mut [(u64, u64)]) -> bool {
+        let mut invalid = _mm256_setzero_si256();
+        let mut pairs = out.chunks_exact_mut(2);
+        let mut levels = raw.chunks_exact(2 * LEVEL_LEN);
+        for (pair, level) in pairs.by_ref().zip(levels.by_ref()) {
+            let (price0, volume0) = load_level(&level[..LEVEL_LEN]);
+            let (price1, volume1) = load_level(&level[LEVEL_LEN..]);
+            let v = _mm256_set_epi64x(volume1 as i64, price1 as i64, volume0 as i64, price0 as i64);
+            let mut lanes = [0u64; 4];
+            _mm256_storeu_si256(
+                lanes.as_mut_ptr() as *mut __m256i,
+                lanes2num_avx2(v, &mut invalid),
+            );
+            pair[0] = (lanes[0], lanes[1]);
+            pair[1] = (lanes[2], lanes[3]);
+        }
+        let valid = _mm256_movemask_epi8(invalid) == 0;
+        match pairs.into_remainder() {
+            [] => valid,
+            rest => levels2num_sse2(levels.remainder(), rest) && valid,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use test_case::test_case;
+
+    const BACKENDS: [Backend; 3] = [Backend::Scalar, Backend::Sse2, Backend::Avx2];
+
+    // the table based decoder every backend must agree with
+    fn levels2num_table(raw: &[u8], n: usize) -> Result<Vec<(u64, u64)>, usize> {
+        (0..n)
+            .map(|i| {
+                let start = i * LEVEL_LEN;
+                let price = bcd::try_bcdarr2num(&raw[start..start + PRICE_LEN])
+                    .map_err(|idx| start + idx)?;
+                let volume = bcd::try_bcdarr2num(&raw[start + PRICE_LEN..start + LEVEL_LEN])
+                    .map_err(|idx| start + PRICE_LEN + idx)?;
+                Ok((price, volume))
+            })
+            .collect()
+    }
+
+    #[test_case(&[0x1, 0x31], Ok(131); "0x01, 0x31 -> 131")]
+    #[test_case(&[0x0, 0x0, 0x85, 0x20, 0x0], Ok(852000); "price")]
+    #[test_case(&[0x99, 0x99, 0x99, 0x99, 0x99, 0x99, 0x99, 0x99], Ok(9999999999999999); "8 bytes")]
+    #[test_case(&[], Ok(0); "empty")]
+    #[test_case(&[0x1, 0x3f], Err(1); "0x01, 0x3f -> err at 1")]
+    #[test_case(&[0xa0, 0x3f], Err(0); "0xa0, 0x3f -> err at 0")]
+    fn try_bcd2num_testcase(input: &[u8], expected: Result<u64, usize>) {
+        assert_eq!(expected, try_bcd2num(input));
+        assert_eq!(bcd::try_bcdarr2num(input), try_bcd2num(input));
+    }
+
+    #[test]
+    fn try_levels2num_test() {
+        let raw = [
+            0x0, 0x0, 0x1, 0x82, 0x0, 0x0, 0x0, 0x0, 0x6, 0x0, 0x0, 0x1, 0x75, 0x0, 0x0, 0x0, 0x0,
+            0x20, 0x0, 0x0, 0x1, 0x93, 0x0, 0x0, 0x0, 0x0, 0x8,
+        ];
+        for backend in BACKENDS.into_iter().filter(Backend::is_available) {
+            let mut out = [(0, 0); 3];
+            assert_eq!(
+                Ok(()),
+                try_levels2num_with(backend, &raw, &mut out),
+                "{:?}",
+                backend
+            );
+            assert_eq!([(18200, 6), (17500, 20), (19300, 8)], out, "{:?}", backend);
+            let mut corrupted = raw;
+            corrupted[22] = 0x1c;
+            assert_eq!(Err(22), try_levels2num_with(backend, &corrupted, &mut out));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn try_bcd2num_proptest(input in prop::collection::vec(any::<u8>(), 0..=8)) {
+            prop_assert_eq!(bcd::try_bcdarr2num(&input), try_bcd2num(&input));
+        }
+
+        #[test]
+        fn try_levels2num_proptest(
+            digits in prop::collection::vec(0..=99u8, 0..=11 * LEVEL_LEN),
+            corrupt in prop::option::of((any::<prop::sample::Index>(), 0xa0..=0xffu8)),
+        ) {
+            let n = digits.len() / LEVEL_LEN;
+            let mut raw: Vec<u8> = digits.into_iter().map(bcd::num2bcd).collect();
+            if let (Some((at, byte)), false) = (corrupt, raw.is_empty()) {
+                let at = at.index(raw.len());
+                raw[at] = byte;
+            }
+            let expected = levels2num_table(&raw, n);
+            for backend in BACKENDS.into_iter().filter(Backend::is_available) {
+                let mut out = vec![(0, 0); n];
+                let result = try_levels2num_with(backend, &raw, &mut out);
+                match &expected {
+                    Ok(levels) => {
+                        prop_assert_eq!(Ok(()), result);
+                        prop_assert_eq!(levels, &out);
+                    }
+                    Err(idx) => prop_assert_eq!(Err(*idx), result),
+                }
+            }
+        }
+    }
+}
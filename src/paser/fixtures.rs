@@ -0,0 +1,36 @@
+//! Records shared by the tests of several modules.
+
+/// Bid only format 6 record of 00632R, numbered 11, with checksum and CR/LF.
+pub const F6_REC: &[u8] = &[
+    0x1b, 0x0, 0x41, 0x1, 0x6, 0x4, 0x0, 0x0, 0x0, 0x11, 0x30, 0x30, 0x36, 0x33, 0x32, 0x52,
+    0x8, 0x30, 0x0, 0x92, 0x9, 0x15, 0x10, 0x0, 0x80, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x6, 0x32,
+    0x0, 0x0, 0x0, 0x0, 0x1, 0x25, 0xd, 0xa,
+];
+
+/// `F6_REC` without the CR/LF.
+pub const F6_RAW: &[u8] = F6_REC.split_at(F6_REC.len() - 2).0;
+
+/// Simulated trade of 1 at 6.32 in 00632R, no bid or ask, without CR/LF.
+pub const F6_TRADE_RAW: &[u8] = &[
+    0x1b, 0x0, 0x41, 0x1, 0x6, 0x4, 0x0, 0x0, 0x0, 0x11, 0x30, 0x30, 0x36, 0x33, 0x32, 0x52,
+    0x8, 0x30, 0x0, 0x92, 0x9, 0x15, 0x80, 0x0, 0x80, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x6, 0x32,
+    0x0, 0x0, 0x0, 0x0, 0x1, 0xb5,
+];
+
+/// Format 1 basic data of 0050, with checksum and CR/LF.
+pub const F1_REC: &[u8] = &[
+    27, 1, 20, 1, 1, 9, 0, 0, 0, 1, 48, 48, 53, 48, 32, 32, 164, 184, 164, 106, 165, 120, 198, 87,
+    53, 48, 32, 32, 32, 32, 32, 32, 48, 48, 32, 32, 32, 32, 0, 48, 0, 1, 65, 149, 0, 0, 1, 86, 16,
+    0, 0, 1, 39, 128, 0, 32, 32, 32, 65, 89, 89, 0, 0, 0, 32, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 32, 0, 16, 0, 32, 32,
+    32, 1, 215, 13, 10,
+];
+
+/// TAIFEX I020 of TXFA4, seq 7, with one further fill.
+pub const I020_RAW: &[u8] = &[
+    0x1b, 0x32, 0x41, 0x8, 0x45, 0x0, 0x12, 0x34, 0x57, 0x0, 0x0, 0x0, 0x7, 0x4, 0x0, 0x58,
+    0x54, 0x58, 0x46, 0x41, 0x34, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20,
+    0x20, 0x20, 0x20, 0x20, 0x20, 0x8, 0x45, 0x0, 0x12, 0x34, 0x56, 0x30, 0x0, 0x0, 0x1, 0x76,
+    0x50, 0x0, 0x0, 0x0, 0x2, 0x1, 0x30, 0x0, 0x0, 0x1, 0x76, 0x51, 0x0, 0x1, 0x0, 0x0, 0x12,
+    0x34, 0x0, 0x0, 0x1, 0x0, 0x0, 0x0, 0x0, 0x99, 0x30, 0xbb, 0xd, 0xa,
+];
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Market a record belongs to. TWSE and TPEx records are told apart by the `cate` byte of the
+/// common header, TAIFEX has a header of its own. TPEx records are framed and sequenced by
+/// that header but not decoded: `Registry::default` leaves them as `Message::Raw` until the
+/// TPEx layouts have been checked against its spec and captured records.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Market {
+    Twse,
+    Tpex,
+    Taifex,
+}
+
+impl Market {
+    pub fn from_cate(cate: u8) -> Option<Market> {
+        match cate {
+            1 => Some(Market::Twse),
+            2 => Some(Market::Tpex),
+            _ => None,
+        }
+    }
+
+    pub fn cate(self) -> Option<u8> {
+        match self {
+            Market::Twse => Some(1),
+            Market::Tpex => Some(2),
+            Market::Taifex => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Market::Twse => "twse",
+            Market::Tpex => "tpex",
+            Market::Taifex => "taifex",
+        }
+    }
+}
+
+impl fmt::Display for Market {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Market {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "twse" | "tse" => Ok(Market::Twse),
+            "tpex" | "otc" => Ok(Market::Tpex),
+            "taifex" => Ok(Market::Taifex),
+            _ => Err(format!("unknown market: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(1, Some(Market::Twse); "twse")]
+    #[test_case(2, Some(Market::Tpex); "tpex")]
+    #[test_case(3, None; "unknown")]
+    fn market_from_cate_testcase(cate: u8, expected: Option<Market>) {
+        assert_eq!(expected, Market::from_cate(cate));
+        if let Some(market) = expected {
+            assert_eq!(Some(cate), market.cate());
+        }
+    }
+
+    #[test_case("twse", Ok(Market::Twse); "twse")]
+    #[test_case("OTC", Ok(Market::Tpex); "otc")]
+    #[test_case("tpex", Ok(Market::Tpex); "tpex")]
+    #[test_case("taifex", Ok(Market::Taifex); "taifex")]
+    #[test_case("sgx", Err(String::from("unknown market: sgx")); "unknown")]
+    fn market_from_str_testcase(input: &str, expected: Result<Market, String>) {
+        assert_eq!(expected, input.parse::<Market>());
+    }
+
+    #[test]
+    fn market_serde_test() {
+        assert_eq!("\"tpex\"", serde_json::to_string(&Market::Tpex).unwrap());
+        assert_eq!(Market::Tpex.to_string(), "tpex");
+        assert_eq!(None, Market::Taifex.cate());
+        assert_eq!(Market::Twse, serde_json::from_str("\"twse\"").unwrap());
+    }
+}
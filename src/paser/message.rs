@@ -0,0 +1,158 @@
+use crate::paser::f1::F1;
+use crate::paser::f12::F12;
+use crate::paser::f3::F3;
+use crate::paser::f6::F6;
+use crate::paser::market::Market;
+use crate::paser::sequence::Gap;
+use crate::paser::taifex::{I010, I020, I080};
+use crate::paser::timestamp::RecvTime;
+use serde::{Deserialize, Serialize};
+
+/// Record of a format no decoder is registered for, passed on undecoded. `market` is the
+/// one of the channel for TAIFEX and the one of `cate` otherwise.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct RawRecord {
+    pub market: Option<Market>,
+    pub cate: u8,
+    pub fcode: u8,
+    pub fver: u8,
+    pub no: u64,
+    pub raw: Vec<u8>,
+}
+
+/// Decoded record broadcast from the receiver to every sink.
+// F6 dominates the size but is also the bulk of the traffic, boxing it would cost an
+// allocation per record
+#[allow(clippy::large_enum_variant)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum Message {
+    F6(F6),
+    F1(F1),
+    F3(F3),
+    F12(F12),
+    I010(I010),
+    I020(I020),
+    I080(I080),
+    Raw(RawRecord),
+    /// Records missing on a channel, sent in place of them.
+    Gap(Gap),
+}
+
+impl Message {
+    /// Redis key / MQTT topic the message is published on.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Message::F6(_) => "f6",
+            Message::F1(_) => "f1",
+            Message::F3(_) => "f3",
+            Message::F12(_) => "f12",
+            Message::I010(_) => "i010",
+            Message::I020(_) => "i020",
+            Message::I080(_) => "i080",
+            Message::Raw(_) => "raw",
+            Message::Gap(_) => "gap",
+        }
+    }
+
+    /// Market from the record's `cate` or protocol, `None` for a category this crate does not
+    /// know.
+    pub fn market(&self) -> Option<Market> {
+        match self {
+            Message::F6(f6) => f6.header.market(),
+            Message::F1(f1) => f1.market(),
+            Message::F3(f3) => f3.market(),
+            Message::F12(f12) => f12.market(),
+            Message::I010(_) | Message::I020(_) | Message::I080(_) => Some(Market::Taifex),
+            Message::Raw(raw) => raw.market,
+            Message::Gap(gap) => Some(gap.market),
+        }
+    }
+
+    /// `key` prefixed with the market, e.g. "tpex:f6" with `sep` ":", so each market gets its
+    /// own Redis key or MQTT topic. TWSE keeps the bare key its consumers already read.
+    pub fn market_key(&self, sep: &str) -> String {
+        match self.market() {
+            Some(Market::Twse) | None => String::from(self.key()),
+            Some(market) => format!("{}{}{}", market, sep, self.key()),
+        }
+    }
+}
+
+/// Serializes as `{"<format>": {...}, "received": "..."}`, so a format 6 message has the
+/// same shape as `F6Received`.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Received {
+    #[serde(flatten)]
+    pub message: Message,
+    pub received: RecvTime,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paser::f6::{bytes2f6, F6Received};
+    use crate::paser::fixtures::F6_RAW;
+
+    #[test]
+    fn received_f6_shape_test() {
+        let f6 = bytes2f6(F6_RAW);
+        let received = RecvTime::from_nanos(1_639_962_000_000_000_000);
+        let rec = Received {
+            message: Message::F6(f6.clone()),
+            received,
+        };
+        let f6rec = F6Received { f6, received };
+        let serialized = serde_json::to_string(&rec).unwrap();
+        assert_eq!(serde_json::to_string(&f6rec).unwrap(), serialized);
+        assert_eq!(rec, serde_json::from_str(&serialized).unwrap());
+    }
+
+    #[test]
+    fn message_market_key_test() {
+        let mut raw = F6_RAW.to_vec();
+        raw[3] = 0x2;
+        let tpex = Message::F6(bytes2f6(&raw));
+        assert_eq!(Some(Market::Tpex), tpex.market());
+        assert_eq!("tpex:f6", tpex.market_key(":"));
+        raw[3] = 0x1;
+        assert_eq!("f6", Message::F6(bytes2f6(&raw)).market_key("/"));
+        let unknown = Message::Raw(RawRecord {
+            market: None,
+            cate: 9,
+            fcode: 6,
+            fver: 4,
+            no: 11,
+            raw: raw.clone(),
+        });
+        assert_eq!((None, String::from("raw")), (unknown.market(), unknown.market_key(":")));
+        let taifex = Message::Raw(RawRecord {
+            market: Some(Market::Taifex),
+            cate: b'2',
+            fcode: b'C',
+            fver: 4,
+            no: 11,
+            raw,
+        });
+        assert_eq!("taifex:raw", taifex.market_key(":"));
+    }
+
+    #[test]
+    fn received_raw_test() {
+        let rec = Received {
+            message: Message::Raw(RawRecord {
+                market: Some(Market::Twse),
+                cate: 1,
+                fcode: 99,
+                fver: 1,
+                no: 7,
+                raw: vec![0x1b, 0x0, 0x13],
+            }),
+            received: RecvTime::from_nanos(1_639_962_000_000_000_000),
+        };
+        assert_eq!(
+            r#"{"raw":{"market":"twse","cate":1,"fcode":99,"fver":1,"no":7,"raw":[27,0,19]},"received":"2021-12-20T09:00:00.000000000+08:00"}"#,
+            serde_json::to_string(&rec).unwrap()
+        );
+    }
+}
@@ -1,2 +1,20 @@
 pub mod bcd;
-pub mod f6;
\ No newline at end of file
+pub mod checksum;
+pub mod depth;
+pub mod error;
+pub mod event;
+pub mod f1;
+pub mod f12;
+pub mod f3;
+pub mod f6;
+pub mod fastbcd;
+#[cfg(test)]
+pub(crate) mod fixtures;
+pub mod market;
+pub mod message;
+pub mod price;
+pub mod registry;
+pub mod sequence;
+pub mod symbol;
+pub mod taifex;
+pub mod timestamp;
\ No newline at end of file
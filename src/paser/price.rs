@@ -0,0 +1,298 @@
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+use std::str::FromStr;
+use std::cell::Cell;
+
+// TWSE prices are 9(5)V9(4)
+pub const PRICE_SCALE: i64 = 10_000;
+const PRICE_DIGITS: usize = 4;
+
+/// Exact price stored as an integer scaled by 10^4.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Price(i64);
+
+impl Price {
+    pub const ZERO: Price = Price(0);
+
+    pub const fn from_scaled(scaled: i64) -> Price {
+        Price(scaled)
+    }
+
+    /// Rounds to the nearest 0.0001.
+    pub fn from_f64(value: f64) -> Price {
+        Price((value * PRICE_SCALE as f64).round() as i64)
+    }
+
+    pub const fn scaled(&self) -> i64 {
+        self.0
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.0 as f64 / PRICE_SCALE as f64
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl Add for Price {
+    type Output = Price;
+
+    fn add(self, rhs: Price) -> Price {
+        Price(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Price {
+    type Output = Price;
+
+    fn sub(self, rhs: Price) -> Price {
+        Price(self.0 - rhs.0)
+    }
+}
+
+impl Neg for Price {
+    type Output = Price;
+
+    fn neg(self) -> Price {
+        Price(-self.0)
+    }
+}
+
+impl Mul<i64> for Price {
+    type Output = Price;
+
+    fn mul(self, rhs: i64) -> Price {
+        Price(self.0 * rhs)
+    }
+}
+
+/// Truncates toward zero past 0.0001.
+impl Div<i64> for Price {
+    type Output = Price;
+
+    fn div(self, rhs: i64) -> Price {
+        Price(self.0 / rhs)
+    }
+}
+
+impl AddAssign for Price {
+    fn add_assign(&mut self, rhs: Price) {
+        self.0 += rhs.0;
+    }
+}
+
+impl SubAssign for Price {
+    fn sub_assign(&mut self, rhs: Price) {
+        self.0 -= rhs.0;
+    }
+}
+
+impl Sum for Price {
+    fn sum<I: Iterator<Item = Price>>(iter: I) -> Price {
+        iter.fold(Price::ZERO, |acc, p| acc + p)
+    }
+}
+
+impl From<Price> for f64 {
+    fn from(price: Price) -> f64 {
+        price.to_f64()
+    }
+}
+
+impl fmt::Display for Price {
+    /// Shortest decimal form, "1.82" for 18200 and "8520" for 85200000.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        let int = abs / PRICE_SCALE as u64;
+        let frac = abs % PRICE_SCALE as u64;
+        if frac == 0 {
+            return write!(f, "{}{}", sign, int);
+        }
+        let frac = format!("{:0width$}", frac, width = PRICE_DIGITS);
+        write!(f, "{}{}.{}", sign, int, frac.trim_end_matches('0'))
+    }
+}
+
+impl FromStr for Price {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Price, String> {
+        let err = || format!("invalid price: {:?}", s);
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+        if int.is_empty() || frac.len() > PRICE_DIGITS {
+            return Err(err());
+        }
+        if !int.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit()) {
+            return Err(err());
+        }
+        let int: i64 = int.parse().map_err(|_| err())?;
+        let frac: i64 = format!("{:0<width$}", frac, width = PRICE_DIGITS)
+            .parse()
+            .map_err(|_| err())?;
+        let scaled = int
+            .checked_mul(PRICE_SCALE)
+            .and_then(|scaled| scaled.checked_add(frac))
+            .ok_or_else(err)?;
+        Ok(Price(if negative { -scaled } else { scaled }))
+    }
+}
+
+/// How `Price` is written by serde: a JSON number (1.82, as quote prices were written before
+/// `Price`), a decimal string ("1.82") or the scaled integer (18200).
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum PriceFormat {
+    #[default]
+    Number,
+    Str,
+    Scaled,
+}
+
+impl FromStr for PriceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "number" | "float" => Ok(PriceFormat::Number),
+            "str" | "string" => Ok(PriceFormat::Str),
+            "scaled" | "int" => Ok(PriceFormat::Scaled),
+            _ => Err(format!("unknown price format: {}", s)),
+        }
+    }
+}
+
+thread_local! {
+    static PRICE_FORMAT: Cell<PriceFormat> = Cell::new(PriceFormat::default());
+}
+
+/// Runs `f`, typically a `serde_json::to_string`, with every `Price` serialized on this thread
+/// written as `format`.
+pub fn with_price_format<T>(format: PriceFormat, f: impl FnOnce() -> T) -> T {
+    let previous = PRICE_FORMAT.with(|cell| cell.replace(format));
+    let result = f();
+    PRICE_FORMAT.with(|cell| cell.set(previous));
+    result
+}
+
+impl Price {
+    fn serialize_as<S: Serializer>(
+        &self,
+        format: PriceFormat,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match format {
+            PriceFormat::Number => serializer.serialize_f64(self.to_f64()),
+            PriceFormat::Str => serializer.collect_str(self),
+            PriceFormat::Scaled => serializer.serialize_i64(self.0),
+        }
+    }
+}
+
+impl Serialize for Price {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.serialize_as(PRICE_FORMAT.with(Cell::get), serializer)
+    }
+}
+
+struct PriceVisitor;
+
+impl<'de> Visitor<'de> for PriceVisitor {
+    type Value = Price;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a decimal string, a scaled integer or a float")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Price, E> {
+        v.parse().map_err(E::custom)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Price, E> {
+        Ok(Price(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Price, E> {
+        i64::try_from(v).map(Price).map_err(E::custom)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Price, E> {
+        Ok(Price::from_f64(v))
+    }
+}
+
+impl<'de> Deserialize<'de> for Price {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Price, D::Error> {
+        deserializer.deserialize_any(PriceVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(18200, "1.82"; "1.82")]
+    #[test_case(85200000, "8520"; "8520")]
+    #[test_case(1, "0.0001"; "0.0001")]
+    #[test_case(0, "0"; "zero")]
+    #[test_case(-6350, "-0.635"; "negative")]
+    fn price_display_testcase(scaled: i64, expected: &str) {
+        assert_eq!(expected, Price::from_scaled(scaled).to_string());
+    }
+
+    #[test_case("1.82", Ok(Price::from_scaled(18200)); "1.82")]
+    #[test_case("8520", Ok(Price::from_scaled(85200000)); "8520")]
+    #[test_case("-0.635", Ok(Price::from_scaled(-6350)); "negative")]
+    #[test_case("1.00001", Err(String::from("invalid price: \"1.00001\"")); "too many digits")]
+    #[test_case("1.8a", Err(String::from("invalid price: \"1.8a\"")); "not a number")]
+    #[test_case(".5", Err(String::from("invalid price: \".5\"")); "no integer part")]
+    #[test_case("922337203685477.5808", Err(String::from("invalid price: \"922337203685477.5808\"")); "overflow")]
+    #[test_case("922337203685477.5807", Ok(Price::from_scaled(i64::MAX)); "max")]
+    fn price_from_str_testcase(input: &str, expected: Result<Price, String>) {
+        assert_eq!(expected, input.parse::<Price>());
+    }
+
+    #[test]
+    fn price_arithmetic_test() {
+        let bid = Price::from_f64(1.82);
+        let ask = Price::from_f64(1.93);
+        assert_eq!(Price::from_scaled(1100), ask - bid);
+        assert_eq!(Price::from_scaled(37500), ask + bid);
+        assert_eq!(Price::from_scaled(36400), bid * 2);
+        assert_eq!(Price::from_scaled(9100), bid / 2);
+        assert_eq!(Price::from_scaled(37500), vec![ask, bid].into_iter().sum());
+        assert!(bid < ask);
+        assert_eq!(1.82, bid.to_f64());
+        assert_eq!(Price::from_f64(1.82), Price::from_f64(1.8199999999));
+    }
+
+    #[test]
+    fn price_serde_test() {
+        let price = Price::from_scaled(18200);
+        let as_json = |format| {
+            price
+                .serialize_as(format, serde_json::value::Serializer)
+                .unwrap()
+        };
+        assert_eq!(serde_json::json!(1.82), as_json(PriceFormat::Number));
+        assert_eq!(serde_json::json!("1.82"), as_json(PriceFormat::Str));
+        assert_eq!(serde_json::json!(18200), as_json(PriceFormat::Scaled));
+        assert_eq!("1.82", serde_json::to_string(&price).unwrap());
+        let scaled = with_price_format(PriceFormat::Scaled, || serde_json::to_string(&price));
+        assert_eq!("18200", scaled.unwrap());
+        assert_eq!("1.82", serde_json::to_string(&price).unwrap());
+        for input in ["\"1.82\"", "18200", "1.82"] {
+            assert_eq!(price, serde_json::from_str::<Price>(input).unwrap());
+        }
+        assert!(serde_json::from_str::<Price>("\"9999999999999999\"").is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use crate::paser::error::ParseError;
+use crate::paser::f6::{bcd_at, check_len, ESC};
+use crate::paser::market::Market;
+use crate::paser::message::{Message, RawRecord};
+use crate::paser::{f1, f12, f6};
+use std::collections::HashMap;
+
+pub type Decoder = fn(&[u8]) -> Result<Message, ParseError>;
+
+/// Maps (cate, fcode, fver) of the common record header to a decoder. Records without a
+/// registered decoder come out as `Message::Raw`.
+pub struct Registry {
+    decoders: HashMap<(u8, u8, u8), Decoder>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry {
+            decoders: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, cate: u8, fcode: u8, fver: u8, decoder: Decoder) {
+        self.decoders.insert((cate, fcode, fver), decoder);
+    }
+
+    pub fn is_registered(&self, cate: u8, fcode: u8, fver: u8) -> bool {
+        self.decoders.contains_key(&(cate, fcode, fver))
+    }
+
+    pub fn decode(&self, raw: &[u8]) -> Result<Message, ParseError> {
+        check_len(raw, 10)?;
+        if raw[0] != ESC {
+            return Err(ParseError::MissingEsc(raw[0]));
+        }
+        let key = (
+            bcd_at(raw, 3, 4)? as u8,
+            bcd_at(raw, 4, 5)? as u8,
+            bcd_at(raw, 5, 6)? as u8,
+        );
+        match self.decoders.get(&key) {
+            Some(decoder) => decoder(raw),
+            None => Ok(Message::Raw(RawRecord {
+                market: Market::from_cate(key.0),
+                cate: key.0,
+                fcode: key.1,
+                fver: key.2,
+                no: bcd_at(raw, 6, 10)?,
+                raw: raw.to_vec(),
+            })),
+        }
+    }
+}
+
+impl Default for Registry {
+    /// Registry with every format this crate can decode, the one place formats are listed.
+    /// Format 3 and TPEx records are left out, their layouts are unverified (see
+    /// `f3::F3_ENTRY_LEN` and `Market`), so they come out as `Message::Raw` rather than as
+    /// guessed values.
+    fn default() -> Registry {
+        let mut registry = Registry::new();
+        registry.register(1, 1, 9, |raw| f1::try_bytes2f1(raw).map(Message::F1));
+        registry.register(1, 6, f6::F6_FVER, |raw| f6::try_bytes2f6(raw).map(Message::F6));
+        registry.register(1, 12, 3, |raw| f12::try_bytes2f12(raw).map(Message::F12));
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paser::fixtures::F6_RAW;
+    use test_case::test_case;
+
+    #[test]
+    fn registry_default_test() {
+        let registry = Registry::default();
+        assert_eq!(
+            Ok(Message::F6(f6::bytes2f6(F6_RAW))),
+            registry.decode(F6_RAW)
+        );
+    }
+
+    #[test_case((1, 1, 9), true; "f1")]
+    #[test_case((1, 3, 4), false; "f3 unverified")]
+    #[test_case((1, 6, 4), true; "f6")]
+    #[test_case((1, 12, 3), true; "f12")]
+    #[test_case((1, 6, 3), false; "f6 version 3")]
+    #[test_case((1, 6, 5), false; "f6 unknown version")]
+    #[test_case((2, 1, 9), false; "tpex f1 unverified")]
+    #[test_case((2, 6, 4), false; "tpex f6 unverified")]
+    #[test_case((3, 6, 4), false; "unknown market")]
+    fn registry_default_testcase(key: (u8, u8, u8), expected: bool) {
+        assert_eq!(expected, Registry::default().is_registered(key.0, key.1, key.2));
+    }
+
+    #[test]
+    fn registry_unknown_test() {
+        let registry = Registry::new();
+        assert_eq!(
+            Ok(Message::Raw(RawRecord {
+                market: Some(Market::Twse),
+                cate: 1,
+                fcode: 6,
+                fver: 4,
+                no: 11,
+                raw: F6_RAW.to_vec(),
+            })),
+            registry.decode(F6_RAW)
+        );
+    }
+
+    #[test]
+    fn registry_register_test() {
+        fn decode_as_raw(raw: &[u8]) -> Result<Message, ParseError> {
+            Ok(Message::Raw(RawRecord {
+                market: None,
+                cate: 0,
+                fcode: 0,
+                fver: 0,
+                no: 0,
+                raw: raw[..1].to_vec(),
+            }))
+        }
+        let mut registry = Registry::default();
+        registry.register(1, 6, 4, decode_as_raw);
+        assert_eq!(decode_as_raw(F6_RAW), registry.decode(F6_RAW));
+    }
+
+    #[test_case(&[0x1b, 0x0, 0x41], ParseError::Truncated { need: 10, got: 3 }; "truncated")]
+    #[test_case(&[0x1b, 0x0, 0x41, 0x1, 0xa6, 0x4, 0x0, 0x0, 0x0, 0x11], ParseError::InvalidBcd { offset: 4, byte: 0xa6 }; "invalid fcode")]
+    fn registry_decode_error_testcase(input: &[u8], expected: ParseError) {
+        assert_eq!(Err(expected), Registry::default().decode(input));
+    }
+}
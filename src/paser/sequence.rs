@@ -0,0 +1,390 @@
+use crate::paser::bcd;
+use crate::paser::market::Market;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+/// Records are numbered per format: (cate, fcode) for TWSE and TPEx, (transmission code,
+/// message kind) for TAIFEX.
+pub type StreamKey = (u8, u8);
+
+// missing ranges remembered per channel to tell late records from duplicates
+const MAX_MISSING_RANGES: usize = 1024;
+// records further behind than this are not taken for duplicates
+const RESET_BEHIND: u64 = 10_000;
+
+/// Whether `seq` arriving when `next` is expected means the exchange numbers the channel
+/// anew, e.g. after restarting its feed, rather than a duplicate.
+pub fn is_reset(next: u64, seq: u64) -> bool {
+    seq.saturating_add(RESET_BEHIND) < next
+}
+
+/// Format a framed record of `market` is numbered in.
+pub fn stream_key(market: Market, raw: &[u8]) -> Option<StreamKey> {
+    let at = match market {
+        Market::Taifex => 1,
+        Market::Twse | Market::Tpex => 3,
+    };
+    Some((*raw.get(at)?, *raw.get(at + 1)?))
+}
+
+/// Stream and sequence number of a framed record of `market`.
+pub fn sequence(market: Market, raw: &[u8]) -> Option<(StreamKey, u64)> {
+    let seq = match market {
+        Market::Taifex => raw.get(9..13)?,
+        Market::Twse | Market::Tpex => raw.get(6..10)?,
+    };
+    Some((stream_key(market, raw)?, bcd::try_bcdarr2num(seq).ok()?))
+}
+
+/// "6" for TWSE format 6, "2A" for TAIFEX futures I020.
+pub fn format_code(market: Market, key: StreamKey) -> String {
+    match market {
+        Market::Taifex => format!("{}{}", key.0 as char, key.1 as char),
+        Market::Twse | Market::Tpex => bcd::bcd2num(key.1).to_string(),
+    }
+}
+
+/// Records `from` to `to`, both included, never arrived on a channel.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Gap {
+    pub market: Market,
+    pub format: String,
+    pub from: u64,
+    pub to: u64,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SeqResult {
+    InOrder,
+    /// Already seen, or from before the channel was first seen.
+    Duplicate,
+    /// Fills part of an earlier gap.
+    Late,
+    /// Ahead of the expected record, the ones in between are missing.
+    Gap(Gap),
+    /// Far behind the expected record, the channel starts over from it. Carries the last
+    /// sequence number before.
+    Reset(u64),
+}
+
+/// Cumulative counts of one channel, or of all with `market` `None`. `missing` sums the gap
+/// sizes, `late` the records that arrived after a gap was reported for them.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
+pub struct ChannelStats {
+    pub market: Option<Market>,
+    pub format: String,
+    pub in_order: u64,
+    pub duplicates: u64,
+    pub late: u64,
+    pub gaps: u64,
+    pub missing: u64,
+    pub resets: u64,
+    pub last: u64,
+}
+
+#[derive(Debug)]
+struct Channel {
+    next: u64,
+    // start -> end of each range still missing
+    missing: BTreeMap<u64, u64>,
+    stats: ChannelStats,
+}
+
+impl Channel {
+    fn fill(&mut self, seq: u64) -> bool {
+        let (start, end) = match self.missing.range(..=seq).next_back() {
+            Some((&start, &end)) if seq <= end => (start, end),
+            _ => return false,
+        };
+        self.missing.remove(&start);
+        if start < seq {
+            self.missing.insert(start, seq - 1);
+        }
+        if seq < end {
+            self.missing.insert(seq + 1, end);
+        }
+        true
+    }
+}
+
+/// Follows the sequence numbers of every (market, format) channel. Shared by the listeners,
+/// which `track` each record, and whatever reports `stats`. Each market is locked on its own,
+/// so listeners of different markets do not wait on each other.
+#[derive(Debug, Default)]
+pub struct SequenceTracker {
+    shards: [Mutex<HashMap<StreamKey, Channel>>; 3],
+}
+
+impl SequenceTracker {
+    pub fn new() -> SequenceTracker {
+        SequenceTracker::default()
+    }
+
+    fn shard(&self, market: Market) -> &Mutex<HashMap<StreamKey, Channel>> {
+        match market {
+            Market::Twse => &self.shards[0],
+            Market::Tpex => &self.shards[1],
+            Market::Taifex => &self.shards[2],
+        }
+    }
+
+    /// Classifies a framed record, `None` if it carries no sequence number.
+    pub fn track(&self, market: Market, raw: &[u8]) -> Option<SeqResult> {
+        let (key, seq) = sequence(market, raw)?;
+        Some(self.observe(market, key, seq))
+    }
+
+    pub fn observe(&self, market: Market, key: StreamKey, seq: u64) -> SeqResult {
+        let mut channels = self.shard(market).lock().unwrap();
+        let channel = channels.entry(key).or_insert_with(|| Channel {
+            next: seq,
+            missing: BTreeMap::new(),
+            stats: ChannelStats {
+                market: Some(market),
+                format: format_code(market, key),
+                ..ChannelStats::default()
+            },
+        });
+        if seq < channel.next {
+            if channel.fill(seq) {
+                channel.stats.late += 1;
+                return SeqResult::Late;
+            }
+            if !is_reset(channel.next, seq) {
+                channel.stats.duplicates += 1;
+                return SeqResult::Duplicate;
+            }
+            let last = channel.stats.last;
+            channel.stats.resets += 1;
+            channel.stats.last = seq;
+            channel.next = seq + 1;
+            channel.missing.clear();
+            return SeqResult::Reset(last);
+        }
+        channel.stats.last = seq;
+        if seq == channel.next {
+            channel.stats.in_order += 1;
+            channel.next += 1;
+            return SeqResult::InOrder;
+        }
+        let (from, to) = (channel.next, seq - 1);
+        channel.stats.gaps += 1;
+        channel.stats.missing += seq - from;
+        channel.next = seq + 1;
+        channel.missing.insert(from, to);
+        if channel.missing.len() > MAX_MISSING_RANGES {
+            channel.missing.pop_first();
+        }
+        SeqResult::Gap(Gap {
+            market,
+            format: channel.stats.format.clone(),
+            from,
+            to,
+        })
+    }
+
+    /// Ranges of `from` to `to` a channel is still missing, in order.
+    pub fn missing(&self, market: Market, key: StreamKey, from: u64, to: u64) -> Vec<(u64, u64)> {
+        let channels = self.shard(market).lock().unwrap();
+        let channel = match channels.get(&key) {
+            Some(channel) => channel,
+            None => return Vec::new(),
+        };
+        let mut missing: Vec<(u64, u64)> = channel
+            .missing
+            .range(..=to)
+            .rev()
+            .take_while(|(_, &end)| end >= from)
+            .map(|(&start, &end)| (start.max(from), end.min(to)))
+            .collect();
+        missing.reverse();
+        missing
+    }
+
+    /// Snapshot of every channel seen so far.
+    pub fn stats(&self) -> Vec<ChannelStats> {
+        let mut stats: Vec<ChannelStats> = Vec::new();
+        for shard in self.shards.iter() {
+            let channels = shard.lock().unwrap();
+            stats.extend(channels.values().map(|channel| channel.stats.clone()));
+        }
+        stats.sort_by_key(|stats| (stats.market.map(Market::as_str), stats.format.clone()));
+        stats
+    }
+
+    /// Counts summed over all channels.
+    pub fn totals(&self) -> ChannelStats {
+        self.stats()
+            .into_iter()
+            .fold(ChannelStats::default(), |mut total, stats| {
+                total.in_order += stats.in_order;
+                total.duplicates += stats.duplicates;
+                total.late += stats.late;
+                total.gaps += stats.gaps;
+                total.missing += stats.missing;
+                total.resets += stats.resets;
+                total
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    const F6: StreamKey = (0x1, 0x6);
+
+    fn gap(from: u64, to: u64) -> SeqResult {
+        SeqResult::Gap(Gap {
+            market: Market::Twse,
+            format: String::from("6"),
+            from,
+            to,
+        })
+    }
+
+    #[test_case(&[1, 2, 3], vec![SeqResult::InOrder, SeqResult::InOrder, SeqResult::InOrder]; "in order")]
+    #[test_case(&[5, 6, 6, 4], vec![SeqResult::InOrder, SeqResult::InOrder, SeqResult::Duplicate, SeqResult::Duplicate]; "duplicates")]
+    #[test_case(&[1, 4, 5], vec![SeqResult::InOrder, gap(2, 3), SeqResult::InOrder]; "gap")]
+    #[test_case(&[1, 5, 3, 3, 2, 4, 4], vec![
+        SeqResult::InOrder,
+        gap(2, 4),
+        SeqResult::Late,
+        SeqResult::Duplicate,
+        SeqResult::Late,
+        SeqResult::Late,
+        SeqResult::Duplicate,
+    ]; "late fills the gap")]
+    fn observe_testcase(input: &[u64], expected: Vec<SeqResult>) {
+        let tracker = SequenceTracker::new();
+        let results: Vec<SeqResult> = input
+            .iter()
+            .map(|&seq| tracker.observe(Market::Twse, F6, seq))
+            .collect();
+        assert_eq!(expected, results);
+    }
+
+    #[test]
+    fn channels_are_separate_test() {
+        let tracker = SequenceTracker::new();
+        assert_eq!(SeqResult::InOrder, tracker.observe(Market::Twse, F6, 10));
+        assert_eq!(
+            SeqResult::InOrder,
+            tracker.observe(Market::Twse, (0x1, 0x1), 1)
+        );
+        assert_eq!(
+            SeqResult::InOrder,
+            tracker.observe(Market::Tpex, (0x2, 0x6), 1)
+        );
+        assert_eq!(SeqResult::InOrder, tracker.observe(Market::Twse, F6, 11));
+        assert_eq!(
+            SeqResult::Gap(Gap {
+                market: Market::Taifex,
+                format: String::from("2A"),
+                from: 2,
+                to: 2,
+            }),
+            [1, 3]
+                .iter()
+                .map(|&seq| tracker.observe(Market::Taifex, (b'2', b'A'), seq))
+                .last()
+                .unwrap()
+        );
+        let formats: Vec<(Option<Market>, String)> = tracker
+            .stats()
+            .into_iter()
+            .map(|stats| (stats.market, stats.format))
+            .collect();
+        assert_eq!(
+            vec![
+                (Some(Market::Taifex), String::from("2A")),
+                (Some(Market::Tpex), String::from("6")),
+                (Some(Market::Twse), String::from("1")),
+                (Some(Market::Twse), String::from("6")),
+            ],
+            formats
+        );
+    }
+
+    #[test]
+    fn stats_test() {
+        let tracker = SequenceTracker::new();
+        for seq in [1, 2, 6, 4, 4, 7, 2] {
+            tracker.observe(Market::Twse, F6, seq);
+        }
+        assert_eq!(
+            ChannelStats {
+                market: Some(Market::Twse),
+                format: String::from("6"),
+                in_order: 3,
+                duplicates: 2,
+                late: 1,
+                gaps: 1,
+                missing: 3,
+                resets: 0,
+                last: 7,
+            },
+            tracker.stats()[0]
+        );
+        assert_eq!((3, 1), (tracker.totals().missing, tracker.totals().late));
+    }
+
+    #[test]
+    fn missing_test() {
+        let tracker = SequenceTracker::new();
+        for seq in [1, 5, 3, 9, 12] {
+            tracker.observe(Market::Twse, F6, seq);
+        }
+        assert_eq!(
+            vec![(2, 2), (4, 4), (6, 8), (10, 11)],
+            tracker.missing(Market::Twse, F6, 1, 12)
+        );
+        assert_eq!(vec![(7, 8), (10, 10)], tracker.missing(Market::Twse, F6, 7, 10));
+        assert_eq!(Vec::<(u64, u64)>::new(), tracker.missing(Market::Tpex, F6, 1, 12));
+    }
+
+    #[test]
+    fn reset_test() {
+        let tracker = SequenceTracker::new();
+        for seq in [499_999, 500_000] {
+            tracker.observe(Market::Twse, F6, seq);
+        }
+        assert_eq!(SeqResult::Duplicate, tracker.observe(Market::Twse, F6, 490_001));
+        // numbered anew, 1 is kept and the channel goes on from it
+        assert_eq!(SeqResult::Reset(500_000), tracker.observe(Market::Twse, F6, 1));
+        assert_eq!(SeqResult::InOrder, tracker.observe(Market::Twse, F6, 2));
+        assert_eq!(gap(3, 3), tracker.observe(Market::Twse, F6, 4));
+        let stats = &tracker.stats()[0];
+        assert_eq!((1, 4, 1), (stats.resets, stats.last, stats.gaps));
+    }
+
+    #[test_case(500_001, 1, true; "renumbered")]
+    #[test_case(500_001, 499_000, false; "duplicate")]
+    #[test_case(10_002, 1, true; "just past")]
+    #[test_case(10_001, 1, false; "just within")]
+    #[test_case(5, 10, false; "ahead")]
+    fn is_reset_testcase(next: u64, seq: u64, expected: bool) {
+        assert_eq!(expected, is_reset(next, seq));
+    }
+
+    #[test]
+    fn missing_ranges_bounded_test() {
+        let tracker = SequenceTracker::new();
+        for i in 0..=MAX_MISSING_RANGES as u64 + 1 {
+            tracker.observe(Market::Twse, F6, 1 + 2 * i);
+        }
+        // the oldest range is forgotten, its record no longer counts as late
+        assert_eq!(SeqResult::Duplicate, tracker.observe(Market::Twse, F6, 2));
+        assert_eq!(SeqResult::Late, tracker.observe(Market::Twse, F6, 4));
+    }
+
+    #[test_case(Market::Twse, &[0x1b, 0x0, 0x41, 0x1, 0x6, 0x4, 0x0, 0x0, 0x0, 0x11], Some(((1, 6), 11)); "twse")]
+    #[test_case(Market::Tpex, &[0x1b, 0x0, 0x41, 0x1, 0x6, 0x4, 0x0, 0x0, 0x0, 0x1a], None; "invalid bcd")]
+    #[test_case(Market::Taifex, &[0x1b, 0x32, 0x41, 0x8, 0x45, 0x0, 0x12, 0x34, 0x57, 0x0, 0x0, 0x0, 0x7], Some(((b'2', b'A'), 7)); "taifex")]
+    #[test_case(Market::Twse, &[0x1b, 0x0, 0x41], None; "too short")]
+    fn sequence_testcase(market: Market, raw: &[u8], expected: Option<(StreamKey, u64)>) {
+        assert_eq!(expected, sequence(market, raw));
+    }
+}
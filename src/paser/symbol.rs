@@ -0,0 +1,138 @@
+use crate::paser::error::ParseError;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::{self, FromStr};
+
+pub const SYMBOL_LEN: usize = 6;
+
+/// Security code as sent on the wire: 6 ASCII bytes padded with trailing spaces. Shown and
+/// serialized without the padding.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol([u8; SYMBOL_LEN]);
+
+impl Symbol {
+    pub fn from_bytes(raw: [u8; SYMBOL_LEN]) -> Result<Symbol, ParseError> {
+        if !raw.is_ascii() {
+            return Err(ParseError::InvalidSymbol(raw));
+        }
+        Ok(Symbol(raw))
+    }
+
+    /// Padded wire form.
+    pub fn as_bytes(&self) -> &[u8; SYMBOL_LEN] {
+        &self.0
+    }
+
+    pub fn as_str(&self) -> &str {
+        // ascii checked on construction
+        str::from_utf8(&self.0).unwrap().trim_end_matches(' ')
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Symbol({:?})", self.as_str())
+    }
+}
+
+impl FromStr for Symbol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Symbol, String> {
+        if s.is_empty() || s.len() > SYMBOL_LEN || !s.is_ascii() {
+            return Err(format!("invalid symbol: {:?}", s));
+        }
+        let mut raw = [b' '; SYMBOL_LEN];
+        raw[..s.len()].copy_from_slice(s.as_bytes());
+        Ok(Symbol(raw))
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+struct SymbolVisitor;
+
+impl<'de> Visitor<'de> for SymbolVisitor {
+    type Value = Symbol;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an ascii symbol of at most 6 characters")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Symbol, E> {
+        v.parse().map_err(E::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Symbol, D::Error> {
+        deserializer.deserialize_str(SymbolVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use test_case::test_case;
+
+    #[test_case(*b"3046  ", "3046"; "padded")]
+    #[test_case(*b"00632R", "00632R"; "full")]
+    fn symbol_from_bytes_testcase(input: [u8; 6], expected: &str) {
+        let symbol = Symbol::from_bytes(input).unwrap();
+        assert_eq!(expected, symbol.as_str());
+        assert_eq!(expected, symbol.to_string());
+        assert_eq!(Ok(symbol), expected.parse());
+        assert_eq!(&input, symbol.as_bytes());
+    }
+
+    #[test]
+    fn symbol_invalid_test() {
+        assert_eq!(
+            Err(ParseError::InvalidSymbol([
+                0x30, 0x30, 0xff, 0x33, 0x32, 0x52
+            ])),
+            Symbol::from_bytes([0x30, 0x30, 0xff, 0x33, 0x32, 0x52])
+        );
+        assert!("".parse::<Symbol>().is_err());
+        assert!("0050000".parse::<Symbol>().is_err());
+        assert!("台積".parse::<Symbol>().is_err());
+    }
+
+    #[test]
+    fn symbol_serde_test() {
+        let symbol: Symbol = "0050".parse().unwrap();
+        assert_eq!("\"0050\"", serde_json::to_string(&symbol).unwrap());
+        assert_eq!(symbol, serde_json::from_str::<Symbol>("\"0050\"").unwrap());
+    }
+
+    #[test]
+    fn symbol_map_key_test() {
+        let mut last = HashMap::new();
+        last.insert(Symbol::from_bytes(*b"2330  ").unwrap(), 1);
+        assert_eq!(Some(&1), last.get(&"2330".parse().unwrap()));
+    }
+}
@@ -0,0 +1,556 @@
+use crate::paser::bcd;
+use crate::paser::depth::Level;
+use crate::paser::error::ParseError;
+use crate::paser::f6::{bcd_at, check_len, BidAsk, ESC, TRAILER_LEN};
+use crate::paser::market::Market;
+use crate::paser::message::{Message, RawRecord};
+use crate::paser::price::{Price, PRICE_SCALE};
+use crate::paser::timestamp::ExchTime;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// None of the layouts below has been checked against the TAIFEX spec yet, and the test
+// records are hand-built rather than captured. A body length other than the one expected is
+// rejected, so a layout that is off fails loudly instead of decoding garbage. Until they are
+// checked against the spec and captured records, MARKETS refuses taifex.
+
+// ESC, transmission code, message kind, time, seq no, version and body length
+pub const HEADER_LEN: usize = 16;
+pub const PROD_ID_LEN: usize = 20;
+// sign byte and 9(9)
+const PRICE_LEN: usize = 6;
+const LEVEL_LEN: usize = PRICE_LEN + 4;
+const I020_FIXED_LEN: usize = 50;
+const I020_FURTHER_LEN: usize = PRICE_LEN + 2;
+const I080_LEN: usize = PROD_ID_LEN + 10 * LEVEL_LEN + 1;
+const I080_DERIVED_LEN: usize = 2 * LEVEL_LEN;
+// prod id, reference price, prod kind, decimal locator, strike price decimal locator, begin
+// date, end date, flow group, delivery date and dynamic banding
+const I010_LEN: usize = PROD_ID_LEN + PRICE_LEN + 17;
+// decimal locators are 9(1)
+const MAX_DECIMALS: u8 = 9;
+
+/// TAIFEX prices are integers with as many implied decimals as the product's decimal
+/// locator, published in its I010 basic data. Kept per decoder, by product id.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DecimalLocators {
+    decimals: HashMap<String, u8>,
+}
+
+impl DecimalLocators {
+    pub fn new() -> DecimalLocators {
+        DecimalLocators::default()
+    }
+
+    pub fn set(&mut self, prod_id: &str, decimals: u8) {
+        self.decimals.insert(String::from(prod_id), decimals);
+    }
+
+    /// Fails for a product whose basic data has not been seen, its prices cannot be scaled.
+    pub fn get(&self, prod_id: &str) -> Result<u8, ParseError> {
+        self.decimals
+            .get(prod_id)
+            .copied()
+            .ok_or_else(|| ParseError::UnknownProduct(String::from(prod_id)))
+    }
+}
+
+fn scale(value: i64, decimals: u8) -> Price {
+    let decimals = decimals as u32;
+    match 10i64.checked_pow(decimals) {
+        Some(div) if div <= PRICE_SCALE => {
+            Price::from_scaled(value.saturating_mul(PRICE_SCALE / div))
+        }
+        // finer than 0.0001, truncated, down to 0 once the divisor no longer fits
+        _ => Price::from_scaled(
+            10i64
+                .checked_pow(decimals - 4)
+                .and_then(|div| value.checked_div(div))
+                .unwrap_or(0),
+        ),
+    }
+}
+
+/// Common TAIFEX header. `trans_code` is '1' for futures and '4' for options basic data, '2'
+/// and '5' for their real-time data; `kind` picks the message within it ('A' basic data or
+/// trade, 'B' best five).
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct TaifexHeader {
+    pub trans_code: char,
+    pub kind: char,
+    pub time: ExchTime,
+    pub seq: u64,
+    pub ver: u8,
+    pub body_len: u16,
+}
+
+impl TaifexHeader {
+    pub fn is_option(&self) -> bool {
+        self.trans_code == '5'
+    }
+}
+
+/// I010, basic data of one product, sent before the market opens.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct I010 {
+    pub header: TaifexHeader,
+    pub prod_id: String,
+    pub reference: Price,
+    pub kind: char,
+    pub decimals: u8,
+    pub strike_decimals: u8,
+}
+
+/// I020, trades of one product. `first` is the first fill of the matching, `further` the
+/// rest in order.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct I020 {
+    pub header: TaifexHeader,
+    pub prod_id: String,
+    pub match_time: ExchTime,
+    pub first: Level,
+    pub further: Vec<Level>,
+    pub total_volume: u64,
+    pub buy_count: u64,
+    pub sell_count: u64,
+    pub status: char,
+}
+
+/// Best bid and ask implied from spread orders, sent along with I080 when present.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct DerivedQuote {
+    pub bid: Level,
+    pub ask: Level,
+}
+
+/// I080, best five bids and asks of one product. Empty levels are left out of `bidask`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct I080 {
+    pub header: TaifexHeader,
+    pub prod_id: String,
+    pub bidask: BidAsk,
+    pub derived: Option<DerivedQuote>,
+}
+
+/// Full record length, header, body, checksum and CR/LF included.
+pub fn try_bytes2len(raw: &[u8]) -> Result<usize, ParseError> {
+    check_len(raw, HEADER_LEN)?;
+    if raw[0] != ESC {
+        return Err(ParseError::MissingEsc(raw[0]));
+    }
+    Ok(HEADER_LEN + bcd_at(raw, 14, 16)? as usize + TRAILER_LEN)
+}
+
+pub fn try_bytes2header(raw: &[u8]) -> Result<TaifexHeader, ParseError> {
+    check_len(raw, HEADER_LEN)?;
+    if raw[0] != ESC {
+        return Err(ParseError::MissingEsc(raw[0]));
+    }
+    // time, then seq no, version and body length
+    bcd_at(raw, 3, 9)?;
+    bcd_at(raw, 9, 16)?;
+    Ok(TaifexHeader {
+        trans_code: raw[1] as char,
+        kind: raw[2] as char,
+        time: ExchTime::from_bcd(raw[3..9].try_into().unwrap()),
+        seq: bcd::bcdarr2num(&raw[9..13]),
+        ver: *bcd::bcd2num(raw[13]) as u8,
+        body_len: bcd::bcdarr2num(&raw[14..16]) as u16,
+    })
+}
+
+fn check_body_len(header: &TaifexHeader, expected: usize) -> Result<(), ParseError> {
+    if header.body_len as usize != expected {
+        return Err(ParseError::LengthMismatch {
+            mlen: HEADER_LEN + header.body_len as usize + TRAILER_LEN,
+            expected: HEADER_LEN + expected + TRAILER_LEN,
+        });
+    }
+    Ok(())
+}
+
+fn prod_id_at(raw: &[u8], start: usize) -> String {
+    String::from_utf8_lossy(&raw[start..start + PROD_ID_LEN])
+        .trim_end()
+        .to_string()
+}
+
+// sign byte, '-' for negative and '0' otherwise, then 9(9)
+fn price_at(raw: &[u8], start: usize, decimals: u8) -> Result<Price, ParseError> {
+    let value = bcd_at(raw, start + 1, start + PRICE_LEN)? as i64;
+    let value = if raw[start] == b'-' { -value } else { value };
+    Ok(scale(value, decimals))
+}
+
+fn level_at(raw: &[u8], start: usize, decimals: u8) -> Result<Level, ParseError> {
+    Ok(Level::new(
+        price_at(raw, start, decimals)?,
+        bcd_at(raw, start + PRICE_LEN, start + LEVEL_LEN)?,
+    ))
+}
+
+pub fn try_bytes2i010(raw: &[u8]) -> Result<I010, ParseError> {
+    let header = try_bytes2header(raw)?;
+    check_body_len(&header, I010_LEN)?;
+    check_len(raw, HEADER_LEN + I010_LEN)?;
+    let body = &raw[HEADER_LEN..];
+    let decimals_at = |start: usize| {
+        let decimals = bcd_at(body, start, start + 1).map_err(|e| shift(e, HEADER_LEN))? as u8;
+        if decimals > MAX_DECIMALS {
+            return Err(ParseError::InvalidDecimals {
+                offset: HEADER_LEN + start,
+                decimals,
+            });
+        }
+        Ok(decimals)
+    };
+    let decimals = decimals_at(PROD_ID_LEN + PRICE_LEN + 1)?;
+    Ok(I010 {
+        header,
+        prod_id: prod_id_at(body, 0),
+        reference: price_at(body, PROD_ID_LEN, decimals).map_err(|e| shift(e, HEADER_LEN))?,
+        kind: body[PROD_ID_LEN + PRICE_LEN] as char,
+        decimals,
+        strike_decimals: decimals_at(PROD_ID_LEN + PRICE_LEN + 2)?,
+    })
+}
+
+pub fn bytes2i020(raw: &[u8], locators: &DecimalLocators) -> I020 {
+    try_bytes2i020(raw, locators).unwrap()
+}
+
+pub fn try_bytes2i020(raw: &[u8], locators: &DecimalLocators) -> Result<I020, ParseError> {
+    let header = try_bytes2header(raw)?;
+    check_len(raw, HEADER_LEN + I020_FIXED_LEN)?;
+    let body = &raw[HEADER_LEN..];
+    let n_further = (body[36] & 0x7F) as usize;
+    let body_len = I020_FIXED_LEN + n_further * I020_FURTHER_LEN;
+    check_body_len(&header, body_len)?;
+    check_len(raw, HEADER_LEN + body_len)?;
+    let prod_id = prod_id_at(body, 0);
+    let decimals = locators.get(&prod_id)?;
+    let further = (0..n_further)
+        .map(|i| {
+            let start = 37 + i * I020_FURTHER_LEN;
+            Ok(Level::new(
+                price_at(body, start, decimals)?,
+                bcd_at(body, start + PRICE_LEN, start + I020_FURTHER_LEN)?,
+            ))
+        })
+        .collect::<Result<Vec<Level>, ParseError>>()
+        .map_err(|e| shift(e, HEADER_LEN))?;
+    let tail = 37 + n_further * I020_FURTHER_LEN;
+    let num = |start: usize, end: usize| bcd_at(body, start, end).map_err(|e| shift(e, HEADER_LEN));
+    num(20, 26)?;
+    Ok(I020 {
+        header,
+        match_time: ExchTime::from_bcd(body[20..26].try_into().unwrap()),
+        first: level_at(body, 26, decimals).map_err(|e| shift(e, HEADER_LEN))?,
+        further,
+        total_volume: num(tail, tail + 4)?,
+        buy_count: num(tail + 4, tail + 8)?,
+        sell_count: num(tail + 8, tail + 12)?,
+        status: body[tail + 12] as char,
+        prod_id,
+    })
+}
+
+pub fn bytes2i080(raw: &[u8], locators: &DecimalLocators) -> I080 {
+    try_bytes2i080(raw, locators).unwrap()
+}
+
+pub fn try_bytes2i080(raw: &[u8], locators: &DecimalLocators) -> Result<I080, ParseError> {
+    let header = try_bytes2header(raw)?;
+    check_len(raw, HEADER_LEN + I080_LEN)?;
+    let body = &raw[HEADER_LEN..];
+    let has_derived = body[I080_LEN - 1] == 0x01;
+    let body_len = I080_LEN + if has_derived { I080_DERIVED_LEN } else { 0 };
+    check_body_len(&header, body_len)?;
+    check_len(raw, HEADER_LEN + body_len)?;
+    let prod_id = prod_id_at(body, 0);
+    let decimals = locators.get(&prod_id)?;
+    let level = |start: usize| level_at(body, start, decimals).map_err(|e| shift(e, HEADER_LEN));
+    let mut bidask = BidAsk::default();
+    for i in 0..5 {
+        let bid = level(PROD_ID_LEN + i * LEVEL_LEN)?;
+        if bid.volume > 0 {
+            bidask.bid.push(bid).expect("five bid levels at most");
+        }
+    }
+    for i in 5..10 {
+        let ask = level(PROD_ID_LEN + i * LEVEL_LEN)?;
+        if ask.volume > 0 {
+            bidask.ask.push(ask).expect("five ask levels at most");
+        }
+    }
+    let derived = match has_derived {
+        true => Some(DerivedQuote {
+            bid: level(I080_LEN)?,
+            ask: level(I080_LEN + LEVEL_LEN)?,
+        }),
+        false => None,
+    };
+    Ok(I080 {
+        header,
+        prod_id,
+        bidask,
+        derived,
+    })
+}
+
+// offsets in errors are relative to the record, not the body
+fn shift(e: ParseError, by: usize) -> ParseError {
+    match e {
+        ParseError::InvalidBcd { offset, byte } => ParseError::InvalidBcd {
+            offset: offset + by,
+            byte,
+        },
+        e => e,
+    }
+}
+
+/// Decodes the TAIFEX records of one channel, learning the decimal locator of every product
+/// from its I010. Trades and quotes of a product not seen in I010 fail with
+/// `ParseError::UnknownProduct`.
+#[derive(Debug, Default)]
+pub struct TaifexDecoder {
+    locators: DecimalLocators,
+}
+
+impl TaifexDecoder {
+    pub fn new() -> TaifexDecoder {
+        TaifexDecoder::default()
+    }
+
+    pub fn locators(&self) -> &DecimalLocators {
+        &self.locators
+    }
+
+    /// Messages other than I010, I020 and I080 come out as `Message::Raw` with the
+    /// transmission code, message kind and version in `cate`, `fcode` and `fver`.
+    pub fn decode(&mut self, raw: &[u8]) -> Result<Message, ParseError> {
+        let header = try_bytes2header(raw)?;
+        match (header.trans_code, header.kind) {
+            ('1' | '4', 'A') => {
+                let i010 = try_bytes2i010(raw)?;
+                self.locators.set(&i010.prod_id, i010.decimals);
+                Ok(Message::I010(i010))
+            }
+            ('2' | '5', 'A') => try_bytes2i020(raw, &self.locators).map(Message::I020),
+            ('2' | '5', 'B') => try_bytes2i080(raw, &self.locators).map(Message::I080),
+            _ => Ok(Message::Raw(RawRecord {
+                market: Some(Market::Taifex),
+                cate: raw[1],
+                fcode: raw[2],
+                fver: header.ver,
+                no: header.seq,
+                raw: raw.to_vec(),
+            })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paser::checksum;
+    use crate::paser::fixtures::I020_RAW;
+    use test_case::test_case;
+
+    fn level(price: f64, volume: u64) -> Level {
+        Level::new(Price::from_f64(price), volume)
+    }
+
+    // I010 of TXOA4, one decimal, reference price 17650
+    const I010_RAW: &[u8] = &[
+        0x1b, 0x31, 0x41, 0x8, 0x45, 0x0, 0x12, 0x34, 0x56, 0x0, 0x0, 0x0, 0x1, 0x4, 0x0, 0x43,
+        0x54, 0x58, 0x4f, 0x41, 0x34, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20,
+        0x20, 0x20, 0x20, 0x20, 0x20, 0x30, 0x0, 0x0, 0x17, 0x65, 0x0, 0x4f, 0x1, 0x0, 0x20, 0x21,
+        0x12, 0x20, 0x20, 0x22, 0x1, 0x19, 0x1, 0x20, 0x22, 0x1, 0x19, 0x59, 0x7a, 0xd, 0xa,
+    ];
+
+    const I080_RAW: &[u8] = &[
+        0x1b, 0x32, 0x42, 0x8, 0x45, 0x0, 0x12, 0x34, 0x57, 0x0, 0x0, 0x0, 0x8, 0x4, 0x1, 0x21,
+        0x54, 0x58, 0x46, 0x41, 0x34, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20,
+        0x20, 0x20, 0x20, 0x20, 0x20, 0x30, 0x0, 0x0, 0x1, 0x76, 0x50, 0x0, 0x0, 0x0, 0x5, 0x30,
+        0x0, 0x0, 0x1, 0x76, 0x49, 0x0, 0x0, 0x0, 0x3, 0x30, 0x0, 0x0, 0x1, 0x76, 0x48, 0x0, 0x0,
+        0x0, 0x1, 0x30, 0x0, 0x0, 0x1, 0x76, 0x47, 0x0, 0x0, 0x0, 0x2, 0x30, 0x0, 0x0, 0x1, 0x76,
+        0x46, 0x0, 0x0, 0x0, 0x10, 0x30, 0x0, 0x0, 0x1, 0x76, 0x51, 0x0, 0x0, 0x0, 0x4, 0x30, 0x0,
+        0x0, 0x1, 0x76, 0x52, 0x0, 0x0, 0x0, 0x1, 0x30, 0x0, 0x0, 0x1, 0x76, 0x53, 0x0, 0x0, 0x0,
+        0x7, 0x30, 0x0, 0x0, 0x1, 0x76, 0x54, 0x0, 0x0, 0x0, 0x2, 0x30, 0x0, 0x0, 0x0, 0x0, 0x0,
+        0x0, 0x0, 0x0, 0x0, 0x0, 0x49, 0xd, 0xa,
+    ];
+
+    // TXFA4, the product of I020_RAW and I080_RAW, quoted in whole points
+    fn futures() -> DecimalLocators {
+        let mut locators = DecimalLocators::new();
+        locators.set("TXFA4", 0);
+        locators
+    }
+
+    fn header(kind: char, seq: u64, body_len: u16) -> TaifexHeader {
+        TaifexHeader {
+            trans_code: '2',
+            kind,
+            time: ExchTime::from_micros(31_500_123_457),
+            seq,
+            ver: 4,
+            body_len,
+        }
+    }
+
+    #[test]
+    fn bytes2i010_test() {
+        assert_eq!(Ok(()), checksum::validate_record(I010_RAW));
+        assert_eq!(
+            Ok(I010 {
+                header: TaifexHeader {
+                    trans_code: '1',
+                    time: ExchTime::from_micros(31_500_123_456),
+                    ..header('A', 1, 43)
+                },
+                prod_id: String::from("TXOA4"),
+                reference: Price::from_f64(17650.),
+                kind: 'O',
+                decimals: 1,
+                strike_decimals: 0,
+            }),
+            try_bytes2i010(I010_RAW)
+        );
+    }
+
+    #[test]
+    fn bytes2i020_test() {
+        assert_eq!(Ok(()), checksum::validate_record(I020_RAW));
+        assert_eq!(Ok(I020_RAW.len()), try_bytes2len(I020_RAW));
+        assert_eq!(
+            I020 {
+                header: header('A', 7, 58),
+                prod_id: String::from("TXFA4"),
+                match_time: ExchTime::from_micros(31_500_123_456),
+                first: level(17650., 2),
+                further: vec![level(17651., 1)],
+                total_volume: 1234,
+                buy_count: 100,
+                sell_count: 99,
+                status: '0',
+            },
+            bytes2i020(I020_RAW, &futures())
+        );
+    }
+
+    #[test]
+    fn bytes2i080_test() {
+        assert_eq!(Ok(()), checksum::validate_record(I080_RAW));
+        assert_eq!(Ok(I080_RAW.len()), try_bytes2len(I080_RAW));
+        let i080 = bytes2i080(I080_RAW, &futures());
+        assert_eq!(header('B', 8, 121), i080.header);
+        assert_eq!("TXFA4", i080.prod_id);
+        assert_eq!(5, i080.bidask.bid.len());
+        assert_eq!(Some(&level(17650., 5)), i080.bidask.best_bid());
+        assert_eq!(Some(&level(17646., 10)), i080.bidask.bid.get(4));
+        // the fifth ask is empty
+        assert_eq!(4, i080.bidask.ask.len());
+        assert_eq!(Some(&level(17651., 4)), i080.bidask.best_ask());
+        assert_eq!(Some(Price::from_f64(1.)), i080.bidask.spread());
+        assert_eq!(None, i080.derived);
+    }
+
+    #[test]
+    fn i080_derived_test() {
+        let mut raw = I080_RAW[..I080_RAW.len() - 3].to_vec();
+        raw[15] = 0x41;
+        *raw.last_mut().unwrap() = 0x01;
+        raw.extend_from_slice(&[0x30, 0x0, 0x0, 0x1, 0x76, 0x49, 0x0, 0x0, 0x0, 0x1]);
+        raw.extend_from_slice(&[0x2d, 0x0, 0x0, 0x0, 0x0, 0x5, 0x0, 0x0, 0x0, 0x2]);
+        let derived = bytes2i080(&raw, &futures()).derived;
+        assert_eq!(
+            Some(DerivedQuote {
+                bid: level(17649., 1),
+                ask: level(-5., 2),
+            }),
+            derived
+        );
+    }
+
+    #[test]
+    fn decimal_locators_test() {
+        let locators = futures();
+        assert_eq!(Ok(0), locators.get("TXFA4"));
+        assert_eq!(
+            Err(ParseError::UnknownProduct(String::from("TXOA4"))),
+            locators.get("TXOA4")
+        );
+    }
+
+    #[test_case(1765, 0, 17_650_000; "whole")]
+    #[test_case(1765, 1, 1_765_000; "one decimal")]
+    #[test_case(-1765, 4, -1765; "four decimals")]
+    #[test_case(1765, 5, 176; "truncated")]
+    #[test_case(1765, 19, 0; "divisor past i64")]
+    #[test_case(1765, 99, 0; "largest locator byte")]
+    fn scale_testcase(value: i64, decimals: u8, expected: i64) {
+        assert_eq!(Price::from_scaled(expected), scale(value, decimals));
+    }
+
+    #[test]
+    fn decoder_test() {
+        let mut decoder = TaifexDecoder::new();
+        let mut raw = I020_RAW.to_vec();
+        raw[16..21].copy_from_slice(b"TXOA4");
+        // no basic data yet, prices cannot be scaled
+        let unknown = Err(ParseError::UnknownProduct(String::from("TXOA4")));
+        assert_eq!(unknown, decoder.decode(&raw));
+        let i010 = try_bytes2i010(I010_RAW).unwrap();
+        assert_eq!(Ok(Message::I010(i010)), decoder.decode(I010_RAW));
+        match decoder.decode(&raw) {
+            Ok(Message::I020(i020)) => assert_eq!(level(1765., 2), i020.first),
+            decoded => panic!("not I020: {:?}", decoded),
+        }
+        let unknown = Err(ParseError::UnknownProduct(String::from("TXFA4")));
+        assert_eq!(unknown, decoder.decode(I080_RAW));
+        raw[2] = b'C';
+        assert_eq!(
+            Ok(Message::Raw(RawRecord {
+                market: Some(Market::Taifex),
+                cate: b'2',
+                fcode: b'C',
+                fver: 4,
+                no: 7,
+                raw: raw.clone(),
+            })),
+            decoder.decode(&raw)
+        );
+    }
+
+    fn corrupt(raw: &[u8], at: usize, byte: u8) -> Vec<u8> {
+        let mut raw = raw.to_vec();
+        raw[at] = byte;
+        raw
+    }
+
+    #[test_case(I020_RAW[..10].to_vec(), ParseError::Truncated { need: 16, got: 10 }; "truncated header")]
+    #[test_case(I020_RAW[..60].to_vec(), ParseError::Truncated { need: 66, got: 60 }; "truncated body")]
+    #[test_case(corrupt(I020_RAW, 0, 0x1c), ParseError::MissingEsc(0x1c); "missing esc")]
+    #[test_case(corrupt(I020_RAW, 12, 0x0a), ParseError::InvalidBcd { offset: 12, byte: 0x0a }; "invalid bcd seq")]
+    #[test_case(corrupt(I020_RAW, 46, 0xf0), ParseError::InvalidBcd { offset: 46, byte: 0xf0 }; "invalid bcd price")]
+    #[test_case(corrupt(I020_RAW, 52, 0x2), ParseError::LengthMismatch { mlen: 77, expected: 85 }; "length mismatch")]
+    fn try_bytes2i020_error_testcase(input: Vec<u8>, expected: ParseError) {
+        assert_eq!(Err(expected), try_bytes2i020(&input, &futures()))
+    }
+
+    #[test_case(I080_RAW[..100].to_vec(), ParseError::Truncated { need: 137, got: 100 }; "truncated")]
+    #[test_case(corrupt(I080_RAW, 136, 0x1), ParseError::LengthMismatch { mlen: 140, expected: 160 }; "derived flag without derived quote")]
+    #[test_case(corrupt(I080_RAW, 40, 0xaa), ParseError::InvalidBcd { offset: 40, byte: 0xaa }; "invalid bcd price")]
+    fn try_bytes2i080_error_testcase(input: Vec<u8>, expected: ParseError) {
+        assert_eq!(Err(expected), try_bytes2i080(&input, &futures()))
+    }
+
+    #[test_case(corrupt(I010_RAW, 43, 0x23), ParseError::InvalidDecimals { offset: 43, decimals: 23 }; "decimals")]
+    #[test_case(corrupt(I010_RAW, 43, 0x99), ParseError::InvalidDecimals { offset: 43, decimals: 99 }; "largest decimals")]
+    #[test_case(corrupt(I010_RAW, 44, 0x10), ParseError::InvalidDecimals { offset: 44, decimals: 10 }; "strike decimals")]
+    #[test_case(corrupt(I010_RAW, 43, 0x1a), ParseError::InvalidBcd { offset: 43, byte: 0x1a }; "invalid bcd decimals")]
+    fn try_bytes2i010_error_testcase(input: Vec<u8>, expected: ParseError) {
+        assert_eq!(Err(expected), try_bytes2i010(&input))
+    }
+}
@@ -0,0 +1,327 @@
+use crate::paser::bcd;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, SecondsFormat, TimeZone, Timelike, Utc};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cell::Cell;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MICROS_PER_DAY: u64 = 86_400_000_000;
+
+/// Asia/Taipei has no daylight saving, a fixed +08:00 is exact.
+pub fn taipei() -> FixedOffset {
+    FixedOffset::east_opt(8 * 3600).unwrap()
+}
+
+/// How `ExchTime` and `RecvTime` are written by serde: an ISO 8601 string or an integer
+/// (microseconds since midnight and nanoseconds since the epoch respectively).
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum TimeFormat {
+    #[default]
+    Iso,
+    Int,
+}
+
+impl FromStr for TimeFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "iso" | "str" | "string" => Ok(TimeFormat::Iso),
+            "int" => Ok(TimeFormat::Int),
+            _ => Err(format!("unknown time format: {}", s)),
+        }
+    }
+}
+
+thread_local! {
+    static TIME_FORMAT: Cell<TimeFormat> = Cell::new(TimeFormat::default());
+}
+
+/// Runs `f`, typically a `serde_json::to_string`, with every `ExchTime` and `RecvTime`
+/// serialized on this thread written as `format`.
+pub fn with_time_format<T>(format: TimeFormat, f: impl FnOnce() -> T) -> T {
+    let previous = TIME_FORMAT.with(|cell| cell.replace(format));
+    let result = f();
+    TIME_FORMAT.with(|cell| cell.set(previous));
+    result
+}
+
+/// Exchange time of day in microseconds since midnight, Taipei time.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ExchTime(u64);
+
+impl ExchTime {
+    pub const fn from_micros(micros: u64) -> ExchTime {
+        ExchTime(micros)
+    }
+
+    pub fn from_bcd(packbcd_arr: [u8; 6]) -> ExchTime {
+        ExchTime(bcd::bcd2micros(packbcd_arr))
+    }
+
+    pub const fn micros(&self) -> u64 {
+        self.0
+    }
+
+    pub fn to_bcd(&self) -> [u8; 6] {
+        bcd::micros2bcd(self.0)
+    }
+
+    pub fn to_naive_time(&self) -> NaiveTime {
+        NaiveTime::from_num_seconds_from_midnight_opt(
+            (self.0 / 1_000_000) as u32,
+            (self.0 % 1_000_000) as u32 * 1000,
+        )
+        .unwrap_or_else(|| NaiveTime::from_hms_opt(0, 0, 0).unwrap())
+    }
+
+    /// Combines with the trading date into a +08:00 timestamp.
+    pub fn on(&self, date: NaiveDate) -> DateTime<FixedOffset> {
+        taipei()
+            .from_local_datetime(&date.and_time(self.to_naive_time()))
+            .unwrap()
+    }
+}
+
+impl fmt::Display for ExchTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let secs = self.0 / 1_000_000;
+        write!(
+            f,
+            "{:02}:{:02}:{:02}.{:06}",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60,
+            self.0 % 1_000_000
+        )
+    }
+}
+
+impl FromStr for ExchTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ExchTime, String> {
+        let t = NaiveTime::parse_from_str(s, "%H:%M:%S%.f")
+            .map_err(|e| format!("invalid exchange time {:?}: {}", s, e))?;
+        Ok(ExchTime(
+            t.num_seconds_from_midnight() as u64 * 1_000_000 + (t.nanosecond() / 1000) as u64,
+        ))
+    }
+}
+
+impl ExchTime {
+    fn serialize_as<S: Serializer>(
+        &self,
+        format: TimeFormat,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match format {
+            TimeFormat::Iso => serializer.collect_str(self),
+            TimeFormat::Int => serializer.serialize_u64(self.0),
+        }
+    }
+}
+
+impl Serialize for ExchTime {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.serialize_as(TIME_FORMAT.with(Cell::get), serializer)
+    }
+}
+
+struct ExchTimeVisitor;
+
+impl<'de> Visitor<'de> for ExchTimeVisitor {
+    type Value = ExchTime;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a HH:MM:SS.ffffff string or microseconds since midnight")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<ExchTime, E> {
+        v.parse().map_err(E::custom)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<ExchTime, E> {
+        if v >= MICROS_PER_DAY {
+            return Err(E::custom(format!("{} micros is past midnight", v)));
+        }
+        Ok(ExchTime(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<ExchTime, E> {
+        let v = u64::try_from(v).map_err(E::custom)?;
+        self.visit_u64(v)
+    }
+}
+
+impl<'de> Deserialize<'de> for ExchTime {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ExchTime, D::Error> {
+        deserializer.deserialize_any(ExchTimeVisitor)
+    }
+}
+
+/// Local receive instant in nanoseconds since the Unix epoch.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RecvTime(i64);
+
+impl RecvTime {
+    pub fn now() -> RecvTime {
+        let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        RecvTime(elapsed.as_nanos() as i64)
+    }
+
+    pub const fn from_nanos(nanos: i64) -> RecvTime {
+        RecvTime(nanos)
+    }
+
+    pub const fn nanos(&self) -> i64 {
+        self.0
+    }
+
+    pub fn to_datetime(&self) -> DateTime<FixedOffset> {
+        Utc.timestamp_nanos(self.0).with_timezone(&taipei())
+    }
+}
+
+impl From<DateTime<FixedOffset>> for RecvTime {
+    fn from(dt: DateTime<FixedOffset>) -> RecvTime {
+        RecvTime(dt.timestamp() * 1_000_000_000 + dt.timestamp_subsec_nanos() as i64)
+    }
+}
+
+impl fmt::Display for RecvTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(
+            &self
+                .to_datetime()
+                .to_rfc3339_opts(SecondsFormat::Nanos, false),
+        )
+    }
+}
+
+impl FromStr for RecvTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<RecvTime, String> {
+        DateTime::parse_from_rfc3339(s)
+            .map(RecvTime::from)
+            .map_err(|e| format!("invalid receive time {:?}: {}", s, e))
+    }
+}
+
+impl RecvTime {
+    fn serialize_as<S: Serializer>(
+        &self,
+        format: TimeFormat,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match format {
+            TimeFormat::Iso => serializer.collect_str(self),
+            TimeFormat::Int => serializer.serialize_i64(self.0),
+        }
+    }
+}
+
+impl Serialize for RecvTime {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.serialize_as(TIME_FORMAT.with(Cell::get), serializer)
+    }
+}
+
+struct RecvTimeVisitor;
+
+impl<'de> Visitor<'de> for RecvTimeVisitor {
+    type Value = RecvTime;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an RFC 3339 string or nanoseconds since the epoch")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<RecvTime, E> {
+        v.parse().map_err(E::custom)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<RecvTime, E> {
+        Ok(RecvTime(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<RecvTime, E> {
+        i64::try_from(v).map(RecvTime).map_err(E::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for RecvTime {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<RecvTime, D::Error> {
+        deserializer.deserialize_any(RecvTimeVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case([0x9, 0x0, 0x0, 0x14, 0x8, 0x66], "09:00:00.140866"; "09:00:00.140866")]
+    #[test_case([0x13, 0x30, 0x0, 0x0, 0x0, 0x0], "13:30:00.000000"; "13:30:00.000000")]
+    fn exchtime_display_testcase(input: [u8; 6], expected: &str) {
+        let t = ExchTime::from_bcd(input);
+        assert_eq!(expected, t.to_string());
+        assert_eq!(bcd::bcd2time(input), t.to_string());
+        assert_eq!(Ok(t), expected.parse());
+    }
+
+    #[test]
+    fn exchtime_chrono_test() {
+        let t = ExchTime::from_micros(32_400_140_866);
+        assert_eq!(
+            NaiveTime::from_hms_micro_opt(9, 0, 0, 140_866).unwrap(),
+            t.to_naive_time()
+        );
+        let dt = t.on(NaiveDate::from_ymd_opt(2021, 12, 20).unwrap());
+        assert_eq!("2021-12-20T09:00:00.140866+08:00", dt.to_rfc3339());
+        assert_eq!(1_639_962_000, dt.timestamp());
+    }
+
+    #[test]
+    fn recvtime_test() {
+        let t = RecvTime::from_nanos(1_639_962_000_140_866_123);
+        assert_eq!("2021-12-20T09:00:00.140866123+08:00", t.to_string());
+        assert_eq!(Ok(t), "2021-12-20T01:00:00.140866123Z".parse());
+        assert!(RecvTime::now() > t);
+    }
+
+    #[test]
+    fn time_serde_test() {
+        use serde_json::{json, value::Serializer};
+        let exch = ExchTime::from_micros(32_400_140_866);
+        let recv = RecvTime::from_nanos(1_639_962_000_140_866_123);
+        assert_eq!("\"09:00:00.140866\"", serde_json::to_string(&exch).unwrap());
+        assert_eq!(
+            "\"2021-12-20T09:00:00.140866123+08:00\"",
+            serde_json::to_string(&recv).unwrap()
+        );
+        assert_eq!(
+            json!(32400140866u64),
+            exch.serialize_as(TimeFormat::Int, Serializer).unwrap()
+        );
+        assert_eq!(
+            json!(1639962000140866123i64),
+            recv.serialize_as(TimeFormat::Int, Serializer).unwrap()
+        );
+        let int = with_time_format(TimeFormat::Int, || serde_json::to_string(&(exch, recv)));
+        assert_eq!("[32400140866,1639962000140866123]", int.unwrap());
+        assert_eq!("\"09:00:00.140866\"", serde_json::to_string(&exch).unwrap());
+        for input in ["\"09:00:00.140866\"", "32400140866"] {
+            assert_eq!(exch, serde_json::from_str::<ExchTime>(input).unwrap());
+        }
+        for input in [
+            "\"2021-12-20T09:00:00.140866123+08:00\"",
+            "1639962000140866123",
+        ] {
+            assert_eq!(recv, serde_json::from_str::<RecvTime>(input).unwrap());
+        }
+        assert!(serde_json::from_str::<ExchTime>("86400000000").is_err());
+    }
+}